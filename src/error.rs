@@ -1,9 +1,12 @@
+use crate::value::Value;
 use std::rc::Rc;
 
+#[derive(Clone)]
 pub struct Error {
     filename: Option<Rc<String>>,
     message: Rc<String>,
-    stack_trace: Vec<Rc<String>>
+    stack_trace: Vec<Rc<String>>,
+    value: Option<Rc<Value>>
 }
 
 
@@ -17,7 +20,25 @@ impl Error {
         Self {
             filename: None,
             message: Rc::new(message.to_string()),
-            stack_trace: vec![]
+            stack_trace: vec![],
+            value: None
+        }
+    }
+
+
+    pub fn raise(value: &Rc<Value>) -> Self {
+        /* Creates an error raising a liszp error value */
+
+        let message = match &**value {
+            Value::Error { message, .. } => message.clone(),
+            _ => format!("raised non-error value '{}'", value)
+        };
+
+        Self {
+            filename: None,
+            message: Rc::new(message),
+            stack_trace: vec![],
+            value: Some(value.clone())
         }
     }
 
@@ -38,27 +59,30 @@ impl Error {
 
         Self {
             filename,
-            message: Rc::clone(&self.message),
-            stack_trace
+            message,
+            stack_trace,
+            value: self.value.clone()
         }
     }
 
 
-    pub fn add_stack_trace_step<S: ToString>(&self, function_name: Option<S>) -> Self {
-        /* Adds a new element to the stack trace */
+    pub fn add_stack_trace_steps<S: ToString>(&self, function_names: Vec<Option<S>>) -> Self {
+        /* Adds new elements to the stack trace, innermost function first */
 
-        let mut stack_trace = Vec::with_capacity(self.stack_trace.len());
+        let mut stack_trace = Vec::with_capacity(self.stack_trace.len() + function_names.len());
 
         for line in self.stack_trace.iter() {
             stack_trace.push(Rc::clone(line));
         }
 
-        stack_trace.push(Rc::new(
-            match function_name {
-                Some(fname) => format!("-> in function '{}'", fname.to_string()),
-                None => "-> in lambda function".into()
-            }
-        ));
+        for function_name in function_names.into_iter() {
+            stack_trace.push(Rc::new(
+                match function_name {
+                    Some(fname) => format!("-> in function '{}'", fname.to_string()),
+                    None => "-> in lambda function".into()
+                }
+            ));
+        }
 
         Self {
             filename: match &self.filename {
@@ -66,7 +90,24 @@ impl Error {
                 None => None
             },
             message: Rc::clone(&self.message),
-            stack_trace
+            stack_trace,
+            value: self.value.clone()
+        }
+    }
+
+
+    /* Conversion */
+
+
+    pub fn to_value(&self) -> Rc<Value> {
+        /* Returns the liszp value a handler receives when catching self */
+
+        match &self.value {
+            Some(v) => v.clone(),
+            None => Value::Error {
+                message: (*self.message).clone(),
+                payload: Value::Nil.rc()
+            }.rc()
         }
     }
 
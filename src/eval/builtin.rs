@@ -1,17 +1,45 @@
 use crate::error::Error;
-use crate::eval::Evaluator;
+use crate::eval::frame::{ GeneratorState, Parameter, Promise, PromiseState };
 use crate::new_error;
 use crate::value::Value;
+use std::cell::{ Cell, RefCell };
 use std::rc::Rc;
 
 
-pub fn car(args: &Vec<Rc<Value>>, evaluator: &mut Evaluator) -> Result<Rc<Value>, Error> {
+pub fn append(args: &[Rc<Value>]) -> Result<Rc<Value>, Error> {
+    /* (append '(a b) '(c) .. tail) -> '(a b c .. . tail) */
+
+    let (tail, lists) = match args.split_last() {
+        Some(split) => split,
+        None => return Ok(Value::Nil.rc())
+    };
+
+    let mut elements = vec![];
+
+    for list in lists.iter() {
+        match list.to_list() {
+            Some(xs) => elements.extend(xs),
+            None => return new_error!("Liszp: function 'append' expected lists, received '{}'", list).into()
+        }
+    }
+
+    let mut result = tail.clone();
+
+    for x in elements.iter().rev() {
+        result = Value::cons(x, &result).rc();
+    }
+
+    Ok(result)
+}
+
+
+pub fn car(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
     /* Gets the car of a cons pair */
 
     match args.as_slice() {
         [cons] => {
-            match &*evaluator.eval(cons)? {
-                Value::Cons { car, .. } => Ok(car.clone()),
+            match &**cons {
+                Value::Cons { car, .. } => Ok(car.borrow().clone()),
                 _ => new_error!("Liszp: function 'car' expected to receive cons pair").into()
             }
         },
@@ -21,13 +49,13 @@ pub fn car(args: &Vec<Rc<Value>>, evaluator: &mut Evaluator) -> Result<Rc<Value>
 }
 
 
-pub fn cdr(args: &Vec<Rc<Value>>, evaluator: &mut Evaluator) -> Result<Rc<Value>, Error> {
+pub fn cdr(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
     /* Gets the cdr of a cons pair */
 
     match args.as_slice() {
         [cons] => {
-            match &*evaluator.eval(cons)? {
-                Value::Cons { cdr, .. } => Ok(cdr.clone()),
+            match &**cons {
+                Value::Cons { cdr, .. } => Ok(cdr.borrow().clone()),
                 _ => new_error!("Liszp: function 'cdr' expected to receive cons pair").into()
             }
         },
@@ -37,100 +65,196 @@ pub fn cdr(args: &Vec<Rc<Value>>, evaluator: &mut Evaluator) -> Result<Rc<Value>
 }
 
 
-pub fn cons(args: &Vec<Rc<Value>>, evaluator: &mut Evaluator) -> Result<Rc<Value>, Error> {
+pub fn cons(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
     /* Creates a cons pair */
 
     match args.as_slice() {
-        [car, cdr] => {
-            let car = evaluator.eval(car)?;
-            let cdr = evaluator.eval(cdr)?;
-
-            Ok(Value::cons(&car, &cdr).rc())
-        },
+        [car, cdr] => Ok(Value::cons(car, cdr).rc()),
 
         _ => new_error!("Liszp: function 'cons' expected 2 arguments").into()
     }
 }
 
 
-pub fn eval_quoted(args: &Vec<Rc<Value>>, evaluator: &mut Evaluator) -> Result<Rc<Value>, Error> {
-    /* Evaluates a quoted value */
+pub fn error_message(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
+    /* Gets the message of an error value */
 
     match args.as_slice() {
-        [quoted_value] => {
-            let value_to_evaluate = evaluator.eval(quoted_value)?;
+        [error] => {
+            match &**error {
+                Value::Error { message, .. } => Ok(Value::String(format!("\"{}\"", message)).rc()),
+                _ => new_error!("Liszp: function 'error-message' expected to receive an error").into()
+            }
+        },
+
+        _ => new_error!("Liszp: function 'error-message' takes 1 argument").into()
+    }
+}
 
-            evaluator.eval(&value_to_evaluate)
+
+pub fn error_payload(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
+    /* Gets the payload of an error value */
+
+    match args.as_slice() {
+        [error] => {
+            match &**error {
+                Value::Error { payload, .. } => Ok(payload.clone()),
+                _ => new_error!("Liszp: function 'error-payload' expected to receive an error").into()
+            }
         },
 
-        _ => new_error!("Liszp: function 'quote' takes exactly one argument").into()
+        _ => new_error!("Liszp: function 'error-payload' takes 1 argument").into()
     }
 }
 
 
-pub fn if_expr(args: &Vec<Rc<Value>>, evaluator: &mut Evaluator) -> Result<Rc<Value>, Error> {
-    /* Evaluates an if expression */
+pub fn generator_is_done(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
+    /* Returns whether a generator has finished evaluating its body */
 
     match args.as_slice() {
-        [cond, true_case, false_case] => {
-            if let Value::Bool(b) = &*evaluator.eval(cond)? {
-                if *b {
-                    evaluator.eval(true_case)
-                } else {
-                    evaluator.eval(false_case)
-                }
-            } else {
-                new_error!("if expression expected a boolean condition").into()
+        [value] => {
+            match &**value {
+                Value::Generator(generator) => {
+                    let res = matches!(&*generator.state.borrow(), GeneratorState::Done);
+
+                    Ok(Value::Bool(res).rc())
+                },
+
+                _ => new_error!("Liszp: function 'generator-done?' expected a generator, received '{}'", value).into()
             }
         },
 
-        _ => new_error!("Liszp: if expression has syntax (if <condition> <true case> <false case>)").into()
+        _ => new_error!("Liszp: function 'generator-done?' takes exactly one argument").into()
+    }
+}
+
+
+pub fn fresh_name(prefix: &str) -> String {
+   /* Creates a name which is different from every other name
+    *
+    * The name contains '#', which the reader never includes in a name, so it
+    * can't be written in source code.
+    */
+
+    thread_local! {
+        static COUNTER: Cell<usize> = const { Cell::new(0) };
     }
+
+    let count = COUNTER.with(|c| {
+        c.set(c.get() + 1);
+        c.get()
+    });
+
+    format!("#:{}{}", prefix, count)
+}
+
+
+pub fn gensym(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
+    /* Creates a fresh name, with an optional prefix, which can't capture or be captured by any other */
+
+    match args.as_slice() {
+        [] => Ok(Value::Name(fresh_name("g")).rc()),
+        [prefix] if prefix.name() != "" => Ok(Value::Name(fresh_name(&prefix.name())).rc()),
+        [prefix] => new_error!("Liszp: function 'gensym' expected a name as a prefix, received '{}'", prefix).into(),
+        _ => new_error!("Liszp: function 'gensym' takes at most one argument").into()
+    }
+}
+
+
+pub fn make_box(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
+    /* Creates a mutable box holding a value */
+
+    match args.as_slice() {
+        [value] => Ok(Value::Box(RefCell::new(value.clone())).rc()),
+        _ => new_error!("Liszp: function 'box' takes exactly one argument").into()
+    }
+}
+
+
+pub fn make_error(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
+   /* Raises a new error with a message and optional payload
+    *
+    * The message is kept without the quotes of a string, as in the errors
+    * raised by the interpreter itself.
+    */
+
+    let (message, payload) = match args.as_slice() {
+        [message] => (message, Value::Nil.rc()),
+        [message, payload] => (message, payload.clone()),
+        _ => return new_error!("Liszp: expected syntax (error <message> <payload>?)").into()
+    };
+
+    let message = match &**message {
+        Value::String(s) => s[1..s.len() - 1].to_string(),
+        _ => message.to_string()
+    };
+
+    let error = Value::Error { message, payload };
+
+    Error::raise(&error.rc()).into()
 }
 
 
-pub fn make_list(args: &Vec<Rc<Value>>, evaluator: &mut Evaluator) -> Result<Rc<Value>, Error> {
+pub fn make_list(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
     /* (list a b c ...) -> '(a b c ...) */
 
-    let mut list = Value::Nil.rc();
+    Ok(Value::cons_list(args))
+}
 
-    for arg in args.iter().rev() {
-        list = Rc::new(
-            Value::Cons {
-                car: evaluator.eval(arg)?,
-                cdr: list
-            }
-        );
+
+pub fn make_parameter(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
+    /* Creates a parameter, which returns its value when called with no arguments */
+
+    match args.as_slice() {
+        [value] => Ok(Value::Parameter(Rc::new(Parameter { value: value.clone() })).rc()),
+        _ => new_error!("Liszp: function 'make-parameter' takes exactly one argument").into()
     }
+}
 
-    Ok(list)
+
+pub fn make_promise(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
+    /* Creates a promise which has already been forced to a value, or returns a promise unchanged */
+
+    match args.as_slice() {
+        [value] => {
+            match &**value {
+                Value::Promise(_) => Ok(value.clone()),
+
+                _ => {
+                    let promise = Promise { state: RefCell::new(PromiseState::Forced(value.clone())) };
+
+                    Ok(Value::Promise(Rc::new(promise)).rc())
+                }
+            }
+        },
+
+        _ => new_error!("Liszp: function 'make-promise' takes exactly one argument").into()
+    }
 }
 
 
-pub fn panic(args: &Vec<Rc<Value>>, evaluator: &mut Evaluator) -> Result<Rc<Value>, Error> {
+pub fn panic(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
     /* Panics */
 
     match args.as_slice() {
-        [msg] => panic!("{}", evaluator.eval(msg)?),
+        [msg] => panic!("{}", msg),
         _ => new_error!("Liszp: expected syntax (panic <message>)").into()
     }
 }
 
 
-pub fn print_value(args: &Vec<Rc<Value>>, evaluator: &mut Evaluator, newline: bool) -> Result<Rc<Value>, Error> {
+pub fn print_value(args: &Vec<Rc<Value>>, newline: bool) -> Result<Rc<Value>, Error> {
     /* Prints a value, optionally with a newline */
 
     match args.as_slice() {
         [value] => {
-            let evaluated = evaluator.eval(value)?;
-
             if newline {
-                println!("{}", &evaluated);
+                println!("{}", value);
             } else {
-                print!("{}", &evaluated);
+                print!("{}", value);
             }
         
-            Ok(evaluated)
+            Ok(value.clone())
         },
 
         _ => new_error!("Function print{} takes 1 argument only", if newline { "ln" } else { "" }).into()
@@ -148,31 +272,132 @@ pub fn quote_value(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
 }
 
 
-pub fn values_are_equal(args: &Vec<Rc<Value>>, evaluator: &mut Evaluator) -> Result<Rc<Value>, Error> {
-    /* Compares two values */
+pub fn raise(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
+    /* Raises a value as an error */
+
+    match args.as_slice() {
+        [value] => Error::raise(value).into(),
+        _ => new_error!("Liszp: function 'raise' takes exactly one argument").into()
+    }
+}
+
+
+pub fn repr(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
+    /* Returns the value printed in place of a value, which is itself unless repr has been extended */
+
+    match args.as_slice() {
+        [value] => Ok(value.clone()),
+        _ => new_error!("Liszp: function 'repr' takes exactly one argument").into()
+    }
+}
+
+
+pub fn set_box(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
+    /* Replaces the value held by a box */
+
+    match args.as_slice() {
+        [b, value] => {
+            match &**b {
+                Value::Box(contents) => {
+                    *contents.borrow_mut() = value.clone();
+
+                    Ok(Value::Nil.rc())
+                },
+
+                _ => new_error!("Liszp: function 'set-box!' expected to receive a box").into()
+            }
+        },
+
+        _ => new_error!("Liszp: function 'set-box!' takes 2 arguments").into()
+    }
+}
+
+
+pub fn set_car(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
+    /* Replaces the car of a cons pair */
+
+    match args.as_slice() {
+        [cons, value] => {
+            match &**cons {
+                Value::Cons { car, .. } => {
+                    *car.borrow_mut() = value.clone();
+
+                    Ok(Value::Nil.rc())
+                },
+
+                _ => new_error!("Liszp: function 'set-car!' expected to receive cons pair").into()
+            }
+        },
+
+        _ => new_error!("Liszp: function 'set-car!' takes 2 arguments").into()
+    }
+}
+
+
+pub fn set_cdr(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
+    /* Replaces the cdr of a cons pair */
 
     match args.as_slice() {
-        [x, y] => {
-            let x = evaluator.eval(x)?;
-            let y = evaluator.eval(y)?;
+        [cons, value] => {
+            match &**cons {
+                Value::Cons { cdr, .. } => {
+                    *cdr.borrow_mut() = value.clone();
+
+                    Ok(Value::Nil.rc())
+                },
 
-            Ok(Value::Bool(x == y).rc())
+                _ => new_error!("Liszp: function 'set-cdr!' expected to receive cons pair").into()
+            }
         },
 
+        _ => new_error!("Liszp: function 'set-cdr!' takes 2 arguments").into()
+    }
+}
+
+
+pub fn type_of(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
+    /* Returns the name of the type of a value */
+
+    match args.as_slice() {
+        [value] => Ok(Value::Name(value.type_name()).rc()),
+        _ => new_error!("Liszp: function 'type-of' takes exactly one argument").into()
+    }
+}
+
+
+pub fn unbox(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
+    /* Gets the value held by a box */
+
+    match args.as_slice() {
+        [b] => {
+            match &**b {
+                Value::Box(contents) => Ok(contents.borrow().clone()),
+                _ => new_error!("Liszp: function 'unbox' expected to receive a box").into()
+            }
+        },
+
+        _ => new_error!("Liszp: function 'unbox' takes 1 argument").into()
+    }
+}
+
+
+pub fn values_are_equal(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
+    /* Compares two values */
+
+    match args.as_slice() {
+        [x, y] => Ok(Value::Bool(x == y).rc()),
+
         _ => new_error!("Liszp: Function 'equals?' takes exactly 2 parameters").into()
     }
 }
 
 
-pub fn value_is_bool(args: &Vec<Rc<Value>>, evaluator: &mut Evaluator) -> Result<Rc<Value>, Error> {
+pub fn value_is_bool(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
     /* Returns whether a value is a bool */
 
     match args.as_slice() {
         [value] => {
-            let res = match &*evaluator.eval(value)? {
-                Value::Bool(_) => true,
-                _ => false
-            };
+            let res = matches!(&**value, Value::Bool(_));
 
             Ok(Value::Bool(res).rc())
         },
@@ -182,15 +407,27 @@ pub fn value_is_bool(args: &Vec<Rc<Value>>, evaluator: &mut Evaluator) -> Result
 }
 
 
-pub fn value_is_cons(args: &Vec<Rc<Value>>, evaluator: &mut Evaluator) -> Result<Rc<Value>, Error> {
+pub fn value_is_box(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
+    /* Returns whether a value is a box */
+
+    match args.as_slice() {
+        [value] => {
+            let res = matches!(&**value, Value::Box(_));
+
+            Ok(Value::Bool(res).rc())
+        },
+
+        _ => new_error!("Liszp: function 'box?' takes exactly one argument").into()
+    }
+}
+
+
+pub fn value_is_cons(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
     /* Returns whether a value is a cons pair */
 
     match args.as_slice() {
         [value] => {
-            let res = match &*evaluator.eval(value)? {
-                Value::Cons {..} => true,
-                _ => false
-            };
+            let res = matches!(&**value, Value::Cons {..});
 
             Ok(Value::Bool(res).rc())
         },
@@ -200,15 +437,27 @@ pub fn value_is_cons(args: &Vec<Rc<Value>>, evaluator: &mut Evaluator) -> Result
 }
 
 
-pub fn value_is_float(args: &Vec<Rc<Value>>, evaluator: &mut Evaluator) -> Result<Rc<Value>, Error> {   
+pub fn value_is_error(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
+    /* Returns whether a value is an error */
+
+    match args.as_slice() {
+        [value] => {
+            let res = matches!(&**value, Value::Error { .. });
+
+            Ok(Value::Bool(res).rc())
+        },
+
+        _ => new_error!("Liszp: function 'error?' takes exactly one argument").into()
+    }
+}
+
+
+pub fn value_is_float(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {   
     /* Returns whether a value is a float */
 
     match args.as_slice() {
         [value] => {
-            let res = match &*evaluator.eval(value)? {
-                Value::Float(_) => true,
-                _ => false
-            };
+            let res = matches!(&**value, Value::Float(_));
 
             Ok(Value::Bool(res).rc())
         },
@@ -218,15 +467,27 @@ pub fn value_is_float(args: &Vec<Rc<Value>>, evaluator: &mut Evaluator) -> Resul
 }
 
 
-pub fn value_is_int(args: &Vec<Rc<Value>>, evaluator: &mut Evaluator) -> Result<Rc<Value>, Error> {
+pub fn value_is_generator(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
+    /* Returns whether a value is a generator */
+
+    match args.as_slice() {
+        [value] => {
+            let res = matches!(&**value, Value::Generator(_));
+
+            Ok(Value::Bool(res).rc())
+        },
+
+        _ => new_error!("Liszp: function 'generator?' takes exactly one argument").into()
+    }
+}
+
+
+pub fn value_is_int(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
     /* Returns whether a value is an int */
 
     match args.as_slice() {
         [value] => {
-            let res = match &*evaluator.eval(value)? {
-                Value::Integer(_) => true,
-                _ => false
-            };
+            let res = matches!(&**value, Value::Integer(_));
 
             Ok(Value::Bool(res).rc())
         },
@@ -236,15 +497,12 @@ pub fn value_is_int(args: &Vec<Rc<Value>>, evaluator: &mut Evaluator) -> Result<
 }
 
 
-pub fn value_is_nil(args: &Vec<Rc<Value>>, evaluator: &mut Evaluator) -> Result<Rc<Value>, Error> {
+pub fn value_is_nil(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
     /* Returns whether a value is nil */
 
     match args.as_slice() {
         [value] => {
-            let res = match &*evaluator.eval(value)? {
-                Value::Nil => true,
-                _ => false
-            };
+            let res = matches!(&**value, Value::Nil);
 
             Ok(Value::Bool(res).rc())
         },
@@ -272,15 +530,27 @@ pub fn value_is_name(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
 }
 
 
-pub fn value_is_str(args: &Vec<Rc<Value>>, evaluator: &mut Evaluator) -> Result<Rc<Value>, Error> {
+pub fn value_is_promise(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
+    /* Returns whether a value is a promise */
+
+    match args.as_slice() {
+        [value] => {
+            let res = matches!(&**value, Value::Promise(_));
+
+            Ok(Value::Bool(res).rc())
+        },
+
+        _ => new_error!("Liszp: function 'promise?' takes exactly one argument").into()
+    }
+}
+
+
+pub fn value_is_str(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
     /* Returns whether a value is a str */
 
     match args.as_slice() {
         [value] => {
-            let res = match &*evaluator.eval(value)? {
-                Value::String(_) => true,
-                _ => false
-            };
+            let res = matches!(&**value, Value::String(_));
 
             Ok(Value::Bool(res).rc())
         },
@@ -0,0 +1,167 @@
+use crate::value::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+
+pub struct Environment {
+   /* A frame of bindings, looked up before those of the frames enclosing it
+    *
+    * Frames are reference counted and never collected otherwise. A closure
+    * holds the frame it was created in, so a closure bound in that frame or
+    * one it encloses, such as a local recursive function defined with
+    * letrec, makes a cycle which keeps the frame and everything it binds
+    * alive after the closure is no longer reachable.
+    * Holding those self-references weakly instead would drop a frame that
+    * is still needed by a closure returned from it, so the cycle is left in
+    * place: a program calling such a function repeatedly grows by a frame
+    * chain per call, which is freed when the program exits.
+    */
+
+    values: RefCell<HashMap<String, Rc<Value>>>,
+    aliases: RefCell<HashMap<String, String>>,
+
+    // names bound to the variables of another namespace, and the names of those variables
+    imports: RefCell<HashMap<String, (Rc<Environment>, String)>>,
+    parent: Option<Rc<Environment>>
+}
+
+
+impl Environment {
+
+    /* Instantiation */
+
+    pub fn new() -> Rc<Self> {
+        /* Creates a new top-level environment */
+
+        Rc::new(Environment {
+            values: RefCell::new(HashMap::new()),
+            aliases: RefCell::new(HashMap::new()),
+            imports: RefCell::new(HashMap::new()),
+            parent: None
+        })
+    }
+
+
+    pub fn new_frame(parent: &Rc<Environment>) -> Rc<Self> {
+        /* Creates an empty frame whose unbound names are looked up in parent */
+
+        Rc::new(Environment {
+            values: RefCell::new(HashMap::new()),
+            aliases: RefCell::new(HashMap::new()),
+            imports: RefCell::new(HashMap::new()),
+            parent: Some(parent.clone())
+        })
+    }
+
+
+    /* Bindings */
+
+
+    pub fn get(&self, name: &str) -> Option<Rc<Value>> {
+        /* Looks up a name in this frame and then in each enclosing frame */
+
+        if let Some(value) = self.values.borrow().get(name) {
+            return Some(value.clone());
+        }
+
+        let alias = self.aliases.borrow().get(name).cloned();
+
+        if let Some(original) = alias {
+            return self.get(&original);
+        }
+
+        let import = self.imports.borrow().get(name).cloned();
+
+        if let Some((namespace, original)) = import {
+            return namespace.get(&original);
+        }
+
+        match &self.parent {
+            Some(parent) => parent.get(name),
+            None => None
+        }
+    }
+
+
+    pub fn define<S: ToString>(&self, name: S, value: Rc<Value>) -> Option<Rc<Value>> {
+        /* Binds a name in this frame, returning any value it replaces */
+
+        self.values.borrow_mut().insert(name.to_string(), value)
+    }
+
+
+    pub fn set(&self, name: &str, value: Rc<Value>) -> bool {
+        /* Rebinds a name in the innermost frame that binds it, returning whether one did */
+
+        if let Some(slot) = self.values.borrow_mut().get_mut(name) {
+            *slot = value;
+
+            return true;
+        }
+
+        let alias = self.aliases.borrow().get(name).cloned();
+
+        if let Some(original) = alias {
+            return self.set(&original, value);
+        }
+
+        let import = self.imports.borrow().get(name).cloned();
+
+        if let Some((namespace, original)) = import {
+            return namespace.set(&original, value);
+        }
+
+        match &self.parent {
+            Some(parent) => parent.set(name, value),
+            None => false
+        }
+    }
+
+
+    pub fn define_alias<S: ToString>(&self, alias: S, original: S) {
+       /* Binds a name in this frame to whatever another name means from this frame
+        *
+        * Looking up or assigning to the alias looks up or assigns to the
+        * original name, starting from this frame rather than from the frame
+        * the alias is used in.
+        */
+
+        self.aliases.borrow_mut().insert(alias.to_string(), original.to_string());
+    }
+
+
+    pub fn define_import<S: ToString>(&self, name: S, namespace: &Rc<Environment>, original: S) {
+       /* Binds a name in this frame to a variable of another namespace
+        *
+        * The name refers to the variable itself rather than to its value, so
+        * assignments to the variable, from either namespace, are seen
+        * through both names.
+        */
+
+        self.imports.borrow_mut().insert(name.to_string(), (namespace.clone(), original.to_string()));
+    }
+
+
+    pub fn names(&self) -> Vec<String> {
+        /* Returns the names bound in this frame */
+
+        self.values.borrow().keys().cloned().collect()
+    }
+
+
+    pub fn remove(&self, name: &str) -> Option<Rc<Value>> {
+        /* Removes a binding from this frame */
+
+        self.values.borrow_mut().remove(name)
+    }
+}
+
+
+impl std::fmt::Debug for Environment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // closures are stored in the environments they capture, so the
+        // bindings themselves can't be printed without looping forever
+        write!(f, "<environment>")
+    }
+}
@@ -1,29 +1,68 @@
 use crate::read;
 use crate::error::Error;
-use crate::eval::{ builtin, operators };
+use crate::eval::{ builtin, operators, Environment, GenericFunction, Signature, StructOperation, StructType, Syntax };
+use crate::eval::frame::{ Callee, Catch, Continuation, Control, DynamicBinding, Frame, Generator, GeneratorState, Parameter, Promise, PromiseState, Restart, Unwinding };
+use crate::eval::generic::Method;
+use crate::eval::module::{ self, Import, Module, ModuleDeclaration, Package };
+use crate::eval::pattern::Clause;
 use crate::new_error;
 use crate::macros;
 use crate::value::Value;
-use std::collections::HashMap;
-use std::path::Path;
+use std::cell::RefCell;
+use std::collections::{ HashMap, HashSet };
+use std::path::{ Path, PathBuf };
 use std::rc::Rc;
 
 
-type ValueMap = HashMap<String, Rc<Value>>;
+const DEFAULT_STACK_LIMIT: usize = 1_000_000;
+
+
+// given an unhandled error and the names and parameters of the active restarts,
+// returns an expression to evaluate in its place or None to let the error unwind
+pub type RestartPrompt = fn(&mut Evaluator, &Error, &[(String, String)]) -> Option<Rc<Value>>;
+
 
 pub struct Evaluator {
+    dynamic_bindings: Option<Rc<DynamicBinding>>,
     evaluated: Vec<Rc<Value>>,
-    env: ValueMap,
+    extended_builtins: HashSet<String>,
+    globals: Rc<Environment>,
+    loading: Vec<PathBuf>,
     macros: HashMap<String, macros::Macro>,
+    prelude_macros: HashMap<String, macros::Macro>,
+    module_declaration: Option<ModuleDeclaration>,
+    modules: HashMap<PathBuf, Rc<Module>>,
+    prelude: Rc<Environment>,
+    readtable: read::Readtable,
+    restart_prompt: Option<RestartPrompt>,
+    stack_limit: usize,
+    trace_expansions: bool,
+    types_defined: usize,
+    warn_macro_redefinition: bool,
 }
 
 
 impl Evaluator {
     pub fn new() -> Self {
+        let prelude = Environment::new();
+
         Evaluator {
+            dynamic_bindings: None,
             evaluated: vec![],
-            env: HashMap::new(),
+            extended_builtins: HashSet::new(),
+            globals: prelude.clone(),
+            loading: vec![],
             macros: HashMap::new(),
+            prelude_macros: HashMap::new(),
+            module_declaration: None,
+            modules: HashMap::new(),
+            prelude,
+            readtable: read::default_readtable(),
+            restart_prompt: None,
+            stack_limit: DEFAULT_STACK_LIMIT,
+            trace_expansions: false,
+            types_defined: 0,
+            warn_macro_redefinition: false,
         }
     }
 
@@ -31,20 +70,59 @@ impl Evaluator {
     pub fn load_stdlib(&mut self) -> Result<(), Error> {
         /* Loads standard macros and functions into the namespace */
 
-        self.eval_file("liszp-stdlib/std-macros.lzp", true)?;
-        self.eval_file("liszp-stdlib/std-functions.lzp", true)?;
+        // expansions within the standard library aren't traced
+        let trace_expansions = std::mem::replace(&mut self.trace_expansions, false);
 
-        Ok(())
+        let stdlib = module::stdlib_dir();
+
+        let result = module::PRELUDE_FILENAMES.iter()
+                                               .try_for_each(|filename| self.eval_file(stdlib.join(filename).display().to_string(), true));
+
+        self.trace_expansions = trace_expansions;
+
+        // the program and each module it imports get their own namespace on top of the standard library
+        self.globals = Environment::new_frame(&self.prelude);
+        self.prelude_macros = self.macros.clone();
+
+        result
+    }
+
+
+    pub fn set_stack_limit(&mut self, stack_limit: usize) {
+        /* Sets the number of frames the evaluation stack may hold before erroring */
+
+        self.stack_limit = stack_limit;
+    }
+
+
+    pub fn set_restart_prompt(&mut self, restart_prompt: RestartPrompt) {
+        /* Sets the function asked to choose a restart when an error isn't handled */
+
+        self.restart_prompt = Some(restart_prompt);
+    }
+
+
+    pub fn set_trace_expansions(&mut self, trace_expansions: bool) {
+        /* Sets whether each macro expansion is logged with its input and output forms */
+
+        self.trace_expansions = trace_expansions;
+    }
+
+
+    pub fn set_warn_macro_redefinition(&mut self, warn_macro_redefinition: bool) {
+        /* Sets whether a warning is printed when a macro is redefined */
+
+        self.warn_macro_redefinition = warn_macro_redefinition;
     }
 
 
     /* Getters */
 
 
-    pub fn get_env(&mut self) -> &mut ValueMap {
-        /* Returns a mutable reference to env */
+    pub fn get_globals(&self) -> &Rc<Environment> {
+        /* Returns the global environment */
 
-        &mut self.env
+        &self.globals
     }
 
 
@@ -55,36 +133,24 @@ impl Evaluator {
     }
 
 
-    /* Env-related functions */
+    pub fn get_readtable(&mut self) -> &mut read::Readtable {
+        /* Returns a mutable reference to the readtable */
 
+        &mut self.readtable
+    }
 
-    fn define_value(&mut self, args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
-        /* Defines a value in self.globals */
-    
-        if args.len() != 2 {
-            return new_error!("Liszp: expected syntax (def <name> <value>)").into();
-        }
 
-        let name = &args[0];
-        let value = self.eval(&args[1])?;
-    
-        if let Value::Name(name) = &**name {
-            if let Value::Lambda { args, body, .. } = &*value {
-                let new_function = Value::Lambda {
-                    args: args.clone(),
-                    body: body.clone(),
-                    name: Some(name.clone())
-                };
+    pub fn trace_expansions(&self) -> bool {
+        /* Returns whether macro expansions are logged */
 
-                self.env.insert(name.clone(), new_function.rc());
-            } else {
-                self.env.insert(name.clone(), value.clone());
-            }
-        } else {
-            return new_error!("Liszp: expected name in def expression").into();
-        }
+        self.trace_expansions
+    }
 
-        Ok(Value::Nil.rc())
+
+    pub fn warn_macro_redefinition(&self) -> bool {
+        /* Returns whether redefining a macro prints a warning */
+
+        self.warn_macro_redefinition
     }
 
 
@@ -92,17 +158,122 @@ impl Evaluator {
 
 
     pub fn preprocess(&mut self, expr: &Rc<Value>) -> Result<Rc<Value>, Error> {
-        /* Preprocesses an expression */
+       /* Expands the macros in an expression and parses it before it is evaluated
+        *
+        * Expressions are expanded once, when they are evaluated from the top
+        * level or by eval, so the bodies of functions are already expanded
+        * when they are called rather than being expanded on every call.
+        * The signatures of lambda expressions and the clauses of match
+        * expressions are parsed here too.
+        */
+
+        let expanded = macros::recursively_expand_macros(expr, self)?;
+
+        Ok(self.parse_syntax(&expanded))
+    }
+
+
+    fn parse_syntax(&self, expr: &Rc<Value>) -> Rc<Value> {
+       /* Replaces the lambda and match expressions within an expression by their parsed syntax
+        *
+        * Patterns are parsed in the global environment, as the names of
+        * constructors are looked up when parsing. Clauses which can't be
+        * parsed yet, such as those using a type defined later, are left to
+        * be parsed when the match expression is first evaluated, which also
+        * reports any errors in them, and clauses are parsed again if a type
+        * has been defined since. Lambda expressions whose signatures can't be
+        * parsed are left to report the error when they are evaluated.
+        */
+
+        let components = match expr.to_list() {
+            Some(xs) if !xs.is_empty() => xs,
+            _ => return expr.clone()
+        };
+
+        let syntax = match (components[0].name().as_str(), components.as_slice()) {
+            ("quote", _) => return expr.clone(),
+
+            ("lambda", [_kwd_lambda, params, body @ ..]) if !body.is_empty() => {
+                match Signature::parse(params) {
+                    Ok(signature) => {
+                        let body: Vec<Rc<Value>> = body.iter().map(|e| self.parse_syntax(e)).collect();
+
+                        Syntax::Lambda {
+                            signature: Rc::new(signature),
+                            body: Self::sequence(&body),
+                            source: expr.clone()
+                        }
+                    },
+
+                    Err(_) => return expr.clone()
+                }
+            },
+
+            ("match", [_kwd_match, value, clauses @ ..]) => {
+                // the patterns of the clauses are left as they are
+                let clauses: Vec<Rc<Value>> = clauses.iter()
+                                                     .map(|clause| match clause.to_list().as_deref() {
+                                                         Some([pattern, rest @ ..]) => {
+                                                             let mut parsed = vec![ pattern.clone() ];
+
+                                                             parsed.extend(rest.iter().map(|e| self.parse_syntax(e)));
+                                                             Value::cons_list(&parsed)
+                                                         },
+
+                                                         _ => clause.clone()
+                                                     })
+                                                     .collect();
+
+                let parsed = Clause::parse_clauses(&clauses, &self.globals).ok()
+                                                                           .map(|parsed| (self.types_defined, Rc::new(parsed)));
+
+                Syntax::Match {
+                    value: self.parse_syntax(value),
+                    clauses,
+                    parsed: RefCell::new(parsed),
+                    source: expr.clone()
+                }
+            },
+
+            _ => {
+                let components: Vec<Rc<Value>> = components.iter().map(|e| self.parse_syntax(e)).collect();
+
+                return Value::cons_list(&components);
+            }
+        };
+
+        Value::Syntax(Rc::new(syntax)).rc()
+    }
+
+
+    fn macroexpand(&mut self, name: &String, args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
+       /* Evaluates a macroexpand or macroexpand-1 expression
+        *
+        * macroexpand-1 expands a use of a macro once, and macroexpand expands
+        * it until it is no longer a use of a macro. Macros in the arguments
+        * of the result aren't expanded, and other expressions are returned
+        * unchanged.
+        */
+
+        let mut expr = match args.as_slice() {
+            [expr] => expr.clone(),
+            _ => return new_error!("Liszp: function '{}' takes exactly one argument", name).into()
+        };
 
-        let macro_expanded = macros::recursively_expand_macros(expr, self)?;
-        let parsed_lambdas = Self::parse_lambdas(&macro_expanded)?;
+        while let Some(expansion) = macros::expand_once(&expr, self)? {
+            expr = expansion;
 
-        Ok(parsed_lambdas)
+            if name == "macroexpand-1" {
+                break;
+            }
+        }
+
+        Ok(expr)
     }
 
 
-    pub fn parse_lambdas(expr: &Rc<Value>) -> Result<Rc<Value>, Error> {
-        /* Searches an expression for lambda exprs and turns them into Value::Lambda's */
+    pub fn parse_lambdas(expr: &Rc<Value>, env: &Rc<Environment>) -> Result<Rc<Value>, Error> {
+        /* Turns a lambda expression into a closure over env, returning other expressions unchanged */
 
         let components = match expr.to_list() {
             Some(xs) => xs,
@@ -114,61 +285,116 @@ impl Evaluator {
         }
 
         match components.as_slice() {
-            [_kwd_lambda, args, body] => {
-                let arg_names = Self::get_arg_names(args)?;
+            [_kwd_lambda, args, body @ ..] if !body.is_empty() => {
                 let lambda = Value::Lambda {
-                    args: arg_names,
-                    body: body.clone(),
+                    signature: Rc::new(Signature::parse(args)?),
+                    body: Self::sequence(body),
+                    env: env.clone(),
                     name: None
                 };
 
                 Ok(lambda.rc())
             },
 
-            _ => new_error!("lambda expressions take the form (lambda <args> <body>").into()
+            _ => new_error!("lambda expressions take the form (lambda <args> <body>..)").into()
+        }
+    }
+
+
+    fn sequence(exprs: &[Rc<Value>]) -> Rc<Value> {
+        /* Combines a list of expressions into one that evaluates them in order */
+
+        match exprs {
+            [expr] => expr.clone(),
+            _ => Value::cons(&Value::Name("begin".into()).rc(), &Value::cons_list(&exprs.to_vec())).rc()
         }
     }
 
     
-    fn get_arg_names(arg_component: &Rc<Value>) -> Result<Vec<String>, Error> {
-        /* Gets the list of argument names from the argument component */
-
-        match &**arg_component {
-            Value::Cons {..} => {
-                let values_list = arg_component.to_list().unwrap();
-                let mut names = Vec::with_capacity(values_list.len());
-
-                for v in values_list.iter() {
-                    match &**v {
-                        Value::Name(name) => names.push(name.clone()),
-                        _ => return new_error!("Liszp: Expected name in function argument").into()
-                    }
-                }
+    /* Eval */
 
-                Ok(names)
-            }
 
-            Value::Name(name) => {
-                Ok(vec![ name.clone() ])
-            }
+    pub fn eval(&mut self, expr: &Rc<Value>) -> Result<Rc<Value>, Error> {
+        /* Evaluates an expression in the global environment */
 
-            Value::Nil => Ok(vec![]),
+        let globals = self.globals.clone();
+        let expanded = self.preprocess(expr)?;
 
-            _ => new_error!("Liszp: Function expected a list of arguments or a single argument in lambda expression").into()
-        }
+        self.eval_in_env(&expanded, &globals)
     }
 
 
-    /* Eval */
+    fn eval_in_env(&mut self, expr: &Rc<Value>, env: &Rc<Environment>) -> Result<Rc<Value>, Error> {
+       /* Evaluates an expression in an environment
+        *
+        * Rather than recursing on the Rust stack, the evaluator keeps the work
+        * left to do as a stack of frames on the heap. Each step either starts
+        * evaluating an expression or returns a value to the top frame, so the
+        * depth of recursion in liszp code is bounded by self.stack_limit
+        * rather than by the size of the native stack.
+        */
 
+        self.run(Control::Eval(expr.clone(), env.clone()), vec![])
+    }
 
-    pub fn eval(&mut self, expr: &Rc<Value>) -> Result<Rc<Value>, Error> {
-        /* Evaluates an expression */
 
-        let value = self.preprocess(expr)?;
+    pub fn call_function(&mut self, function: &Rc<Value>, args: Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
+        /* Calls a function from Rust, evaluating it to completion on a new stack */
+
+        let mut stack = vec![];
+        let control = self.apply(Callee::Function(function.clone()), args, &mut stack)?;
+
+        self.run(control, stack)
+    }
+
+
+    fn run(&mut self, control: Control, stack: Vec<Frame>) -> Result<Rc<Value>, Error> {
+        /* Runs the evaluator from a control and stack until the stack is empty */
+
+        let mut control = control;
+        let mut stack = stack;
+
+        loop {
+            let step = match control {
+                Control::Unwind(unwinding) => {
+                    // errors that unwind the whole stack end evaluation
+                    control = self.unwind(unwinding, &mut stack)?;
+                    continue;
+                },
+
+                _ if stack.len() > self.stack_limit => {
+                    new_error!("maximum recursion depth of {} exceeded", self.stack_limit).into()
+                },
+
+                Control::Eval(expr, env) => self.eval_step(&expr, &env, &mut stack),
+
+                Control::Return(value) => {
+                    match stack.pop() {
+                        Some(frame) => self.return_to_frame(frame, value, &mut stack),
+                        None => return Ok(value)
+                    }
+                }
+            };
+
+            control = match step {
+                Ok(c) => c,
+                Err(e) => {
+                    let condition = e.to_value();
+
+                    self.signal(condition, Some(e), &mut stack)
+                        .unwrap_or_else(|e| Control::Unwind(Unwinding::Error(e)))
+                }
+            };
+        }
+    }
+
 
-        match &*value {
-            Value::Cons { car: function, cdr: args } => {
+    fn eval_step(&mut self, expr: &Rc<Value>, env: &Rc<Environment>, stack: &mut Vec<Frame>) -> Result<Control, Error> {
+        /* Starts evaluating an expression */
+
+        match &**expr {
+            Value::Cons { .. } => {
+                let (function, args) = expr.uncons().unwrap();
                 let function_name = function.name();
                 let args = match args.to_list() {
                     Some(xs) => xs,
@@ -176,43 +402,248 @@ impl Evaluator {
                 };
 
                 match function_name.as_str() {
-                    "bool?"          => builtin::value_is_bool(&args, self),
-                    "car"            => builtin::car(&args, self),
-                    "cdr"            => builtin::cdr(&args, self),
-                    "cons"           => builtin::cons(&args, self),
-                    "cons?"          => builtin::value_is_cons(&args, self),
-                    "def"            => self.define_value(&args),
-                    "equals?"        => builtin::values_are_equal(&args, self),
-                    "eval"           => builtin::eval_quoted(&args, self),
-                    "float?"         => builtin::value_is_float(&args, self),
-                    "if"             => builtin::if_expr(&args, self),
-                    "int?"           => builtin::value_is_int(&args, self),
-                    "list"           => builtin::make_list(&args, self),
-                    "name?"          => builtin::value_is_name(&args),
-                    "nil?"           => builtin::value_is_nil(&args, self),
-                    "panic"          => builtin::panic(&args, self),
-                    "print"          => builtin::print_value(&args, self, false),
-                    "println"        => builtin::print_value(&args, self, true),
-                    "quote"          => builtin::quote_value(&args),
-                    "str?"           => builtin::value_is_str(&args, self),
-                    "+"|"-"|"*"|"/"  => operators::arithmetic_expression(&function_name, &args, self),
-                    "%"              => operators::modulo(&args, self),
-                    "and"|"or"|"xor" => operators::logical_operation(&function_name, &args, self),
-                    "not"            => operators::logical_negation(&args, self),
-                    "<"|">"|"<="
-                    |">="|"=="|"!="  => operators::comparison(&function_name, &args, self),
-                    _                => self.evaluate_lambda_funcall(function, &args)
+                    "begin"|"do" => Ok(Self::eval_sequence(args, env, stack)),
+                    "def"   => self.eval_define(&args, env, stack),
+                    "defgeneric" => self.eval_defgeneric(&args, env),
+                    "defmethod" => self.eval_defmethod(&args, env),
+                    "defstruct" => self.eval_defstruct(&args),
+                    "deftype" => self.eval_deftype(&args),
+                    "delay" => Self::eval_delay(&args, env),
+                    "eval"  => Self::eval_eval(&args, env, stack),
+                    "generator" => Ok(Self::make_generator(&args, env)),
+                    "if"    => Self::eval_if(&args, env, stack),
+                    "lambda" => Ok(Control::Return(Self::parse_lambdas(expr, env)?)),
+                    "import" => self.eval_import(&args),
+                    "match" => self.eval_match(&args, env, stack),
+                    "module" => self.eval_module(&args),
+                    "parameterize" => self.eval_parameterize(args, env, stack),
+                    "handler-bind" => self.eval_handler_bind(args, env, stack),
+                    "quote" => Ok(Control::Return(builtin::quote_value(&args)?)),
+                    "reset" => Ok(Self::eval_reset(args, env, stack)),
+                    "restart-case" => Self::eval_restart_case(args, env, stack),
+                    "set!"  => Self::eval_assign(&args, env, stack),
+                    "shift" => self.eval_shift(args, env, stack),
+                    "try"   => Self::eval_try(args, env, stack),
+                    _ if Self::is_builtin(&function_name) && !self.is_extended_builtin(&function_name, env) => {
+                        self.eval_args(Callee::Builtin(function_name), args, env, stack)
+                    },
+                    _ => {
+                        stack.push(Frame::Function { args, env: env.clone() });
+
+                        Ok(Control::Eval(function, env.clone()))
+                    }
                 }
             },
 
+            Value::Syntax(syntax) => self.eval_syntax(syntax, env, stack),
+
+            Value::Name(name) if name.len() > 1 && name.starts_with(':') => Ok(Control::Return(expr.clone())),
+
             Value::Name(name) => {
-                match self.env.get(name) {
-                    Some(v) => Ok(v.clone()),
+                match env.get(name) {
+                    Some(v) => Ok(Control::Return(v)),
                     None => new_error!("value '{}' is undefined", name).into()
                 }
             },
 
-            _ => Ok(value.clone())
+            _ => Ok(Control::Return(expr.clone()))
+        }
+    }
+
+
+    fn return_to_frame(&mut self, frame: Frame, value: Rc<Value>, stack: &mut Vec<Frame>) -> Result<Control, Error> {
+        /* Passes a computed value to the frame that was waiting for it */
+
+        match frame {
+            Frame::Assign { name, env } => {
+                if env.set(&name, value) {
+                    Ok(Control::Return(Value::Nil.rc()))
+                } else {
+                    new_error!("cannot assign to unbound name '{}'", name).into()
+                }
+            },
+
+            Frame::Args { callee, mut evaluated, mut remaining, env } => {
+                evaluated.push(value);
+
+                match remaining.pop() {
+                    Some(next_arg) => {
+                        stack.push(Frame::Args { callee, evaluated, remaining, env: env.clone() });
+
+                        Ok(Control::Eval(next_arg, env))
+                    },
+
+                    None => self.apply(callee, evaluated, stack)
+                }
+            },
+
+            Frame::Call { .. } => Ok(Control::Return(value)),
+
+            Frame::Default { name, pending, frame, body } => {
+                frame.define(name, value);
+
+                Ok(Self::eval_defaults(pending, frame, body, stack))
+            },
+
+            Frame::Define { name } => {
+                self.define_value(&name, &value);
+
+                Ok(Control::Return(Value::Nil.rc()))
+            },
+
+            Frame::Eval { env } => Ok(Control::Eval(self.preprocess(&value)?, env)),
+
+            Frame::Force { promise } => {
+                let mut state = promise.state.borrow_mut();
+
+                // the promise may have been forced by its own expression, in which case that value is kept
+                match &*state {
+                    PromiseState::Forced(forced) => Ok(Control::Return(forced.clone())),
+
+                    PromiseState::Delayed { .. } => {
+                        *state = PromiseState::Forced(value.clone());
+
+                        Ok(Control::Return(value))
+                    }
+                }
+            },
+
+            Frame::Generator { generator } => {
+                generator.state.replace(GeneratorState::Done);
+
+                Ok(Control::Return(Value::Nil.rc()))
+            },
+
+            Frame::Guard { value: matched, clauses, index, env, clause_env } => {
+                match &*value {
+                    Value::Bool(true) => Ok(Self::eval_sequence(clauses[index].body.clone(), &clause_env, stack)),
+                    Value::Bool(false) => Self::match_clauses(matched, clauses, index + 1, env, stack),
+                    _ => new_error!("guard of match clause expected a boolean, received '{}'", value).into()
+                }
+            },
+
+            Frame::Handlers { .. } => Ok(Control::Return(value)),
+
+            Frame::Parameterize { outer, .. } => {
+                self.dynamic_bindings = outer;
+
+                Ok(Control::Return(value))
+            },
+
+            Frame::Match { clauses, env } => Self::match_clauses(value, clauses, 0, env, stack),
+
+            Frame::Reset => Ok(Control::Return(value)),
+
+            Frame::Restarts { .. } => Ok(Control::Return(value)),
+
+            Frame::Resume { control } => Ok(control),
+
+            Frame::Signal { condition, error, handlers, .. } => self.call_next_handler(condition, error, handlers, stack),
+
+            Frame::Sequence { remaining, env } => Ok(Self::eval_sequence(remaining, &env, stack)),
+
+            Frame::Try { finally, env, .. } => {
+                if finally.is_empty() {
+                    Ok(Control::Return(value))
+                } else {
+                    stack.push(Frame::Resume { control: Control::Return(value) });
+
+                    Ok(Self::eval_sequence(finally, &env, stack))
+                }
+            }
+
+            Frame::Function { args, env } => self.eval_args(Callee::Function(value), args, &env, stack),
+
+            Frame::Finally { exprs, env } => {
+                stack.push(Frame::Resume { control: Control::Return(value) });
+
+                Ok(Self::eval_sequence(exprs, &env, stack))
+            },
+
+            Frame::If { true_case, false_case, env } => {
+                match &*value {
+                    Value::Bool(true) => Ok(Control::Eval(true_case, env)),
+                    Value::Bool(false) => Ok(Control::Eval(false_case, env)),
+                    _ => new_error!("if expression expected a boolean condition").into()
+                }
+            }
+        }
+    }
+
+
+    fn unwind(&mut self, unwinding: Unwinding, stack: &mut Vec<Frame>) -> Result<Control, Error> {
+       /* Unwinds the stack to the innermost frame that handles the unwinding
+        *
+        * Errors are handled by catch clauses and restarts by the frame that
+        * established them, and finally clauses are evaluated on the way.
+        *
+        * returns
+        * -------
+        * - Ok(control) to continue evaluating from the handling frame
+        * - Err(error)  with a full stack trace if nothing handles an error
+        */
+
+        let mut unwinding = unwinding;
+        let mut function_names = vec![];
+
+        while let Some(frame) = stack.pop() {
+            unwinding = match (frame, unwinding) {
+                (Frame::Call { name }, unwinding) => {
+                    function_names.push(name);
+                    unwinding
+                },
+
+                (Frame::Try { catch: Some(catch), finally, env }, Unwinding::Error(error)) => {
+                    if !finally.is_empty() {
+                        stack.push(Frame::Finally { exprs: finally, env: env.clone() });
+                    }
+
+                    let handler_env = Environment::new_frame(&env);
+
+                    handler_env.define(&catch.name, error.to_value());
+
+                    return Ok(Self::eval_sequence(catch.handler, &handler_env, stack));
+                },
+
+                (Frame::Try { finally, env, .. }, unwinding) | (Frame::Finally { exprs: finally, env }, unwinding) => {
+                    if finally.is_empty() {
+                        unwinding
+                    } else {
+                        let unwinding = match unwinding {
+                            Unwinding::Error(error) => {
+                                Unwinding::Error(error.add_stack_trace_steps(std::mem::take(&mut function_names)))
+                            },
+
+                            restart => restart
+                        };
+
+                        stack.push(Frame::Resume { control: Control::Unwind(unwinding) });
+
+                        return Ok(Self::eval_sequence(finally, &env, stack));
+                    }
+                },
+
+                (Frame::Generator { generator }, unwinding) => {
+                    generator.state.replace(GeneratorState::Done);
+                    unwinding
+                },
+
+                (Frame::Parameterize { outer, .. }, unwinding) => {
+                    self.dynamic_bindings = outer;
+                    unwinding
+                },
+
+                (_, Unwinding::Restart { target, frame, pending_defaults, body }) if stack.len() == target => {
+                    return Ok(Self::eval_defaults(pending_defaults, frame, body, stack));
+                },
+
+                (_, unwinding) => unwinding
+            };
+        }
+
+        match unwinding {
+            Unwinding::Error(error) => Err(error.add_stack_trace_steps(function_names)),
+            Unwinding::Restart { .. } => new_error!("invoked a restart which is no longer active").into()
         }
     }
 
@@ -222,16 +653,38 @@ impl Evaluator {
 
         let filename = filepath.to_string();
 
-        let source = std::fs::read_to_string(filepath)
-                        .expect(format!("Cannot open file '{}'", filename).as_str());
-
-        let read_exprs = match read::read(&source, &filename, stdlib) {
-            Ok(xs) => xs,
-            Err(e) => return e.add_filename(&filename).into()
+        let source = match std::fs::read_to_string(filepath) {
+            Ok(source) => source,
+            Err(e) => return new_error!("Liszp: cannot open file '{}': {}", filename, e).add_filename(&filename).into()
         };
 
-        for expr in read_exprs.iter() {
-            let evaluated = self.eval(expr).map_err(|e| e.add_filename(&filename))?;
+        let mut reader = read::Reader::new(&source, &filename, stdlib);
+
+        // the files being loaded are tracked to resolve relative imports and detect cycles
+        let path = Path::new(&filename).canonicalize().unwrap_or_else(|_| PathBuf::from(&filename));
+
+        self.loading.push(path);
+
+        let result = self.eval_exprs(&mut reader, &filename);
+
+        self.loading.pop();
+
+        result
+    }
+
+
+    fn eval_exprs(&mut self, reader: &mut read::Reader, filename: &String) -> Result<(), Error> {
+        /* Evaluates the expressions read from a source file */
+
+        // each expression is evaluated before the next is read, so that it may define reader macros
+        loop {
+            let expr = match reader.read(self) {
+                Ok(Some(expr)) => expr,
+                Ok(None) => break,
+                Err(e) => return e.add_filename(filename).into()
+            };
+
+            let evaluated = self.eval(&expr).map_err(|e| e.add_filename(filename))?;
 
             self.evaluated.push(evaluated);
         }
@@ -240,59 +693,1624 @@ impl Evaluator {
     }
 
 
-    /* function evaluation */
+    fn set_dispatch_macro(&mut self, args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
+       /* Defines a reader macro, for a call (set-dispatch-macro! <char> <function>)
+        *
+        * The character is a name or string of one character, which can't be
+        * one that starts a comment after a '#'. Afterwards, when a '#'
+        * followed by the character is read, the function is called on the
+        * expression after it, or on the elements of the list if the character
+        * opens one, and the value it returns is read in its place. Unlike
+        * reader macros defined in Rust, the function isn't given the reader's
+        * stream, so the text after the dispatch character must be an
+        * expression the reader can read.
+        */
+
+        let (dispatch_char, function) = match args.as_slice() {
+            [c, function] => {
+                let string = match &**c {
+                    Value::Name(name) => name.clone(),
+                    Value::String(s) => s[1..s.len() - 1].to_string(),
+                    _ => String::new()
+                };
+
+                match string.chars().collect::<Vec<char>>().as_slice() {
+                    [c] if !read::starts_comment(*c) => (*c, function.clone()),
+                    _ => return new_error!("Liszp: set-dispatch-macro! expected a single character which doesn't start a comment, received '{}'", c).into()
+                }
+            },
 
+            _ => return new_error!("Liszp: function 'set-dispatch-macro!' takes exactly two arguments").into()
+        };
 
-    fn evaluate_lambda_funcall(&mut self, function: &Rc<Value>, arg_values: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
-        /* Evaluates the calling of a non-built-in function */
+        let reader_macro: read::ReaderMacro = Rc::new(move |reader, evaluator| {
+            match reader.read_dispatch_argument(dispatch_char, evaluator)? {
+                Some(arg) => Ok(Some(evaluator.call_function(&function, vec![ arg ])?)),
+                None => reader.error_with_reader_position(format!("expected an expression after '#{}'", dispatch_char)).into()
+            }
+        });
 
-        let evaluated_function = self.eval(&function)?;
+        self.readtable.insert(dispatch_char, reader_macro);
 
-        match &*evaluated_function {
-            Value::Lambda { args, body, name } => {
-                let replaced_values = match self.add_args_to_env(&args, arg_values) {
-                    Ok(rvs) => rvs,
-                    Err(e) => return e.add_stack_trace_step(name.as_ref()).into()
-                };
+        Ok(Value::Nil.rc())
+    }
 
-                let result = self.eval(&body);
 
-                self.replace_old_values(&replaced_values);
-        
-                result.map_err(|e| e.add_stack_trace_step(name.as_ref()))
-            }
+    /* Special forms */
 
-            _ => new_error!("expected function, received '{}'", function).into()
+
+    fn eval_sequence(exprs: Vec<Rc<Value>>, env: &Rc<Environment>, stack: &mut Vec<Frame>) -> Control {
+        /* Evaluates a list of expressions in order, returning the value of the last */
+
+        let mut remaining = exprs;
+
+        if remaining.is_empty() {
+            return Control::Return(Value::Nil.rc());
+        }
+
+        let next = remaining.remove(0);
+
+        // the last expression is in tail position, so nothing is left to wait for it
+        if !remaining.is_empty() {
+            stack.push(Frame::Sequence { remaining, env: env.clone() });
+        }
+
+        Control::Eval(next, env.clone())
+    }
+
+
+    fn eval_assign(args: &Vec<Rc<Value>>, env: &Rc<Environment>, stack: &mut Vec<Frame>) -> Result<Control, Error> {
+        /* Evaluates a set! expression */
+
+        match args.as_slice() {
+            [name, value] => {
+                if let Value::Name(name) = &**name {
+                    stack.push(Frame::Assign { name: name.clone(), env: env.clone() });
+
+                    Ok(Control::Eval(value.clone(), env.clone()))
+                } else {
+                    new_error!("Liszp: expected name in set! expression").into()
+                }
+            },
+
+            _ => new_error!("Liszp: expected syntax (set! <name> <value>)").into()
+        }
+    }
+
+
+    fn eval_define(&mut self, args: &Vec<Rc<Value>>, env: &Rc<Environment>, stack: &mut Vec<Frame>) -> Result<Control, Error> {
+        /* Evaluates a def expression */
+
+        match args.as_slice() {
+            [name, value] => {
+                if let Value::Name(name) = &**name {
+                    stack.push(Frame::Define { name: name.clone() });
+
+                    Ok(Control::Eval(value.clone(), env.clone()))
+                } else {
+                    new_error!("Liszp: expected name in def expression").into()
+                }
+            },
+
+            _ => new_error!("Liszp: expected syntax (def <name> <value>)").into()
+        }
+    }
+
+
+    fn define_value(&mut self, name: &String, value: &Rc<Value>) {
+        /* Defines a value in self.globals */
+
+        if let Value::Lambda { signature, body, env, .. } = &**value {
+            let new_function = Value::Lambda {
+                signature: signature.clone(),
+                body: body.clone(),
+                env: env.clone(),
+                name: Some(name.clone())
+            };
+
+            self.globals.define(name, new_function.rc());
+        } else {
+            self.globals.define(name, value.clone());
         }
     }
 
 
-    fn add_args_to_env(&mut self, arg_names: &Vec<String>, arg_values: &Vec<Rc<Value>>) -> Result<ValueMap, Error> {
-        /* Adds the values */
+    fn eval_defstruct(&mut self, args: &[Rc<Value>]) -> Result<Control, Error> {
+       /* Evaluates a defstruct expression
+        *
+        * (defstruct point x y) defines a new struct type with the functions
+        *
+        *   make-point       which creates a point from its fields
+        *   point?           which returns whether a value is a point
+        *   point-x, point-y which get a point's fields
+        *
+        * and set-point-x! and set-point-y! if the type is mutable.
+        */
 
-        if arg_names.len() != arg_values.len() {
-            return new_error!("function expected {} arguments but received {}", arg_names.len(), arg_values.len()).into();
+        let kind = StructType::parse(args)?;
+
+        for (name, function) in kind.functions() {
+            self.globals.define(name, function);
         }
-        
-        let mut replaced_values = HashMap::new();
 
-        for i in 0..arg_names.len() {
-            let evaluated_arg = self.eval(&arg_values[i])?;
+        // patterns parsed before the type was defined may refer to it
+        self.types_defined += 1;
+
+        Ok(Control::Return(Value::Nil.rc()))
+    }
+
 
-            if let Some(old_value) = self.env.insert(arg_names[i].clone(), evaluated_arg) {
-                replaced_values.insert(arg_names[i].clone(), old_value);
+    fn eval_deftype(&mut self, args: &[Rc<Value>]) -> Result<Control, Error> {
+       /* Evaluates a deftype expression
+        *
+        * (deftype option none (some value)) defines a sum type whose values
+        * are either none or created by (some <value>), with the functions
+        *
+        *   option?            which returns whether a value is an option
+        *   none?, some?       which return whether a value is that variant
+        *   some-value         which gets the field of a some
+        */
+
+        let variants = StructType::parse_sum(args)?;
+
+        for variant in variants.iter() {
+            for (name, function) in variant.functions() {
+                self.globals.define(name, function);
             }
         }
 
-        Ok(replaced_values)
+        // patterns parsed before the type was defined may refer to it
+        self.types_defined += 1;
+
+        let predicate_name = format!("{}?", variants[0].type_name());
+
+        let predicate = Value::StructFunction {
+            kind: variants[0].clone(),
+            operation: StructOperation::SumPredicate,
+            name: predicate_name.clone()
+        };
+
+        self.globals.define(predicate_name, predicate.rc());
+
+        Ok(Control::Return(Value::Nil.rc()))
     }
 
 
-    fn replace_old_values(&mut self, replaced_values: &ValueMap) {
-        /* Replaces all new values in self.env with these old ones */
+    fn eval_eval(args: &Vec<Rc<Value>>, env: &Rc<Environment>, stack: &mut Vec<Frame>) -> Result<Control, Error> {
+        /* Evaluates an eval expression */
+
+        match args.as_slice() {
+            [quoted_value] => {
+                stack.push(Frame::Eval { env: env.clone() });
 
-        for key in replaced_values.keys() {
-            self.env.insert(key.clone(), replaced_values.get(key).unwrap().clone());
+                Ok(Control::Eval(quoted_value.clone(), env.clone()))
+            },
+
+            _ => new_error!("Liszp: function 'eval' takes exactly one argument").into()
         }
     }
+
+
+    fn eval_if(args: &Vec<Rc<Value>>, env: &Rc<Environment>, stack: &mut Vec<Frame>) -> Result<Control, Error> {
+        /* Evaluates an if expression */
+
+        match args.as_slice() {
+            [cond, true_case, false_case] => {
+                stack.push(Frame::If {
+                    true_case: true_case.clone(),
+                    false_case: false_case.clone(),
+                    env: env.clone()
+                });
+
+                Ok(Control::Eval(cond.clone(), env.clone()))
+            },
+
+            _ => new_error!("Liszp: if expression has syntax (if <condition> <true case> <false case>)").into()
+        }
+    }
+
+
+    fn eval_match(&mut self, args: &[Rc<Value>], env: &Rc<Environment>, stack: &mut Vec<Frame>) -> Result<Control, Error> {
+       /* Evaluates a match expression which hasn't been preprocessed
+        *
+        * A match expression has the form
+        *
+        *   (match <expr> (<pattern> <body>..)..)
+        *
+        * and evaluates the body of the first clause whose pattern matches
+        * the value of expr, with the names bound by the pattern defined.
+        */
+
+        match args.split_first() {
+            Some((value, clauses)) => {
+                let clauses = Rc::new(Clause::parse_clauses(clauses, env)?);
+
+                Ok(Self::eval_match_clauses(value, clauses, env, stack))
+            },
+
+            None => new_error!("Liszp: expected syntax (match <expr> (<pattern> <body>..)..)").into()
+        }
+    }
+
+
+    fn eval_syntax(&mut self, syntax: &Syntax, env: &Rc<Environment>, stack: &mut Vec<Frame>) -> Result<Control, Error> {
+        /* Evaluates a lambda or match expression which was parsed when it was preprocessed */
+
+        match syntax {
+            Syntax::Lambda { signature, body, .. } => {
+                let lambda = Value::Lambda {
+                    signature: signature.clone(),
+                    body: body.clone(),
+                    env: env.clone(),
+                    name: None
+                };
+
+                Ok(Control::Return(lambda.rc()))
+            },
+
+            Syntax::Match { value, clauses, parsed, .. } => {
+                let current = match &*parsed.borrow() {
+                    Some((types_defined, clauses)) if *types_defined == self.types_defined => Some(clauses.clone()),
+                    _ => None
+                };
+
+                let clauses = match current {
+                    Some(clauses) => clauses,
+                    None => {
+                        let clauses = Rc::new(Clause::parse_clauses(clauses, env)?);
+
+                        parsed.replace(Some((self.types_defined, clauses.clone())));
+                        clauses
+                    }
+                };
+
+                Ok(Self::eval_match_clauses(value, clauses, env, stack))
+            }
+        }
+    }
+
+
+    fn eval_match_clauses(value: &Rc<Value>, clauses: Rc<Vec<Clause>>, env: &Rc<Environment>, stack: &mut Vec<Frame>) -> Control {
+        /* Evaluates the value of a match expression, to be matched against its parsed clauses */
+
+        stack.push(Frame::Match {
+            clauses,
+            env: env.clone()
+        });
+
+        Control::Eval(value.clone(), env.clone())
+    }
+
+
+    fn match_clauses(value: Rc<Value>, clauses: Rc<Vec<Clause>>, start: usize, env: Rc<Environment>, stack: &mut Vec<Frame>) -> Result<Control, Error> {
+        /* Evaluates the first clause from start onwards whose pattern matches a value */
+
+        for index in start..clauses.len() {
+            let clause = &clauses[index];
+            let clause_env = Environment::new_frame(&env);
+
+            if !clause.pattern.bind(&value, &clause_env) {
+                continue;
+            }
+
+            return match &clause.guard {
+                Some(guard) => {
+                    let guard = guard.clone();
+
+                    stack.push(Frame::Guard {
+                        value,
+                        clauses,
+                        index,
+                        env,
+                        clause_env: clause_env.clone()
+                    });
+
+                    Ok(Control::Eval(guard, clause_env))
+                },
+
+                None => Ok(Self::eval_sequence(clause.body.clone(), &clause_env, stack))
+            };
+        }
+
+        new_error!("no clause of match expression matched '{}'", value).into()
+    }
+
+
+    fn eval_try(args: Vec<Rc<Value>>, env: &Rc<Environment>, stack: &mut Vec<Frame>) -> Result<Control, Error> {
+       /* Evaluates a try expression
+        *
+        * A try expression has the form
+        *
+        *   (try <body>.. (catch <name> <handler>..) (finally <cleanup>..))
+        *
+        * where either of the catch and finally clauses may be left out.
+        */
+
+        let mut body = args;
+        let mut catch = None;
+        let mut finally = vec![];
+
+        if let Some(clause) = Self::take_clause(&mut body, "finally") {
+            finally = clause;
+        }
+
+        if let Some(clause) = Self::take_clause(&mut body, "catch") {
+            match clause.split_first() {
+                Some((name, handler)) if name.name() != "" => {
+                    catch = Some(Catch { name: name.name(), handler: handler.to_vec() });
+                },
+
+                _ => return new_error!("Liszp: expected syntax (catch <name> <handler>..)").into()
+            }
+        }
+
+        if catch.is_none() && finally.is_empty() {
+            return new_error!("Liszp: try expressions need a catch or finally clause").into();
+        }
+
+        stack.push(Frame::Try { catch, finally, env: env.clone() });
+
+        Ok(Self::eval_sequence(body, env, stack))
+    }
+
+
+    fn take_clause(exprs: &mut Vec<Rc<Value>>, keyword: &str) -> Option<Vec<Rc<Value>>> {
+        /* Removes a trailing (<keyword> ..) clause from exprs, returning its contents */
+
+        let clause = match exprs.last().and_then(|last| last.to_list()) {
+            Some(xs) if !xs.is_empty() && xs[0].name() == keyword => xs,
+            _ => return None
+        };
+
+        exprs.pop();
+
+        Some(clause[1..].to_vec())
+    }
+
+
+    /* Conditions and restarts */
+
+
+    fn eval_handler_bind(&mut self, args: Vec<Rc<Value>>, env: &Rc<Environment>, stack: &mut Vec<Frame>) -> Result<Control, Error> {
+       /* Evaluates a handler-bind expression
+        *
+        * A handler-bind expression has the form
+        *
+        *   (handler-bind (<handler>..) <body>..)
+        *
+        * where each handler evaluates to a function of one argument. When a
+        * condition is signalled in the body, the handlers are called with it
+        * before anything is unwound, and a handler declines by returning.
+        */
+
+        match args.split_first() {
+            Some((handlers, body)) => {
+                let handlers = match handlers.to_list() {
+                    Some(xs) => xs,
+                    None => return new_error!("Liszp: expected a list of handlers in handler-bind expression").into()
+                };
+
+                let callee = Callee::HandlerBind { body: body.to_vec(), env: env.clone() };
+
+                self.eval_args(callee, handlers, env, stack)
+            },
+
+            None => new_error!("Liszp: expected syntax (handler-bind (<handler>..) <body>..)").into()
+        }
+    }
+
+
+    fn eval_restart_case(args: Vec<Rc<Value>>, env: &Rc<Environment>, stack: &mut Vec<Frame>) -> Result<Control, Error> {
+       /* Evaluates a restart-case expression
+        *
+        * A restart-case expression has the form
+        *
+        *   (restart-case <expr> (<name> <params> <body>..)..)
+        *
+        * and evaluates expr. If one of its restarts is invoked while doing so,
+        * the stack is unwound to here and the value of the restart's body is
+        * returned instead.
+        */
+
+        let (expr, clauses) = match args.split_first() {
+            Some(split) => split,
+            None => return new_error!("Liszp: expected syntax (restart-case <expr> (<name> <params> <body>..)..)").into()
+        };
+
+        let mut restarts = Vec::with_capacity(clauses.len());
+
+        for clause in clauses.iter() {
+            match clause.to_list().as_deref() {
+                Some([name, params, body @ ..]) if name.name() != "" => {
+                    restarts.push(Restart {
+                        name: name.name(),
+                        signature: Rc::new(Signature::parse(params)?),
+                        body: Self::sequence(body)
+                    });
+                },
+
+                _ => return new_error!("Liszp: expected restart clause (<name> <params> <body>..)").into()
+            }
+        }
+
+        stack.push(Frame::Restarts { restarts, env: env.clone() });
+
+        Ok(Control::Eval(expr.clone(), env.clone()))
+    }
+
+
+    fn signal(&mut self, condition: Rc<Value>, error: Option<Error>, stack: &mut Vec<Frame>) -> Result<Control, Error> {
+        /* Calls the active handlers with a condition, innermost first */
+
+        let handlers = Self::active_handlers(stack, error.is_some());
+
+        self.call_next_handler(condition, error, handlers, stack)
+    }
+
+
+    fn call_next_handler(&mut self, condition: Rc<Value>, error: Option<Error>, handlers: Vec<(usize, Rc<Value>)>, stack: &mut Vec<Frame>) -> Result<Control, Error> {
+        /* Calls the next handler for a condition, or gives up if none are left */
+
+        let mut handlers = handlers;
+
+        match handlers.pop() {
+            Some((handler_index, handler)) => {
+                stack.push(Frame::Signal {
+                    condition: condition.clone(),
+                    error,
+                    handlers,
+                    handler_index
+                });
+
+                self.apply(Callee::Function(handler), vec![ condition ], stack)
+            },
+
+            None => {
+                match error {
+                    Some(error) => Ok(self.unhandled_error(error, stack)),
+                    None => Ok(Control::Return(Value::Nil.rc()))
+                }
+            }
+        }
+    }
+
+
+    fn unhandled_error(&mut self, error: Error, stack: &mut Vec<Frame>) -> Control {
+        /* Offers the active restarts for an error no handler dealt with, before it unwinds */
+
+        let prompt = match self.restart_prompt {
+            Some(prompt) => prompt,
+            None => return Control::Unwind(Unwinding::Error(error))
+        };
+
+        let will_be_caught = stack.iter().any(|frame| matches!(frame, Frame::Try { catch: Some(_), .. }));
+
+        let restarts = Self::active_restarts(stack);
+
+        if will_be_caught || restarts.is_empty() {
+            return Control::Unwind(Unwinding::Error(error));
+        }
+
+        match prompt(self, &error, &restarts).map(|expr| self.preprocess(&expr)) {
+            Some(Ok(expr)) => {
+                // if the expression doesn't invoke a restart, the error is offered again
+                stack.push(Frame::Signal {
+                    condition: error.to_value(),
+                    error: Some(error),
+                    handlers: vec![],
+                    handler_index: stack.len()
+                });
+
+                Control::Eval(expr, self.globals.clone())
+            },
+
+            Some(Err(e)) => Control::Unwind(Unwinding::Error(e)),
+
+            None => Control::Unwind(Unwinding::Error(error))
+        }
+    }
+
+
+    fn active_handlers(stack: &[Frame], is_error: bool) -> Vec<(usize, Rc<Value>)> {
+       /* Returns the handlers which may be called, innermost last
+        *
+        * Handlers established by a handler that is running aren't active, and
+        * neither are handlers outside the innermost try expression with a
+        * catch clause when an error is signalled, as it catches the error.
+        */
+
+        let start = match stack.iter().rposition(|frame| matches!(frame, Frame::Try { catch: Some(_), .. })) {
+            Some(i) if is_error => i + 1,
+            _ => 0
+        };
+
+        let limit = stack.iter()
+                         .filter_map(|frame| match frame {
+                             Frame::Signal { handler_index, .. } => Some(*handler_index),
+                             _ => None
+                         })
+                         .min()
+                         .unwrap_or(stack.len());
+
+        let mut active_handlers = vec![];
+
+        for (i, frame) in stack.iter().enumerate().take(limit).skip(start) {
+            if let Frame::Handlers { handlers } = frame {
+                for handler in handlers.iter().rev() {
+                    active_handlers.push((i, handler.clone()));
+                }
+            }
+        }
+
+        active_handlers
+    }
+
+
+    fn active_restarts(stack: &[Frame]) -> Vec<(String, String)> {
+        /* Returns the names and parameters of the active restarts, innermost first */
+
+        let mut restarts = vec![];
+
+        for frame in stack.iter().rev() {
+            if let Frame::Restarts { restarts: established, .. } = frame {
+                for restart in established.iter() {
+                    restarts.push((restart.name.clone(), restart.signature.to_string()));
+                }
+            }
+        }
+
+        restarts
+    }
+
+
+    fn invoke_restart(args: Vec<Rc<Value>>, stack: &mut [Frame]) -> Result<Control, Error> {
+        /* Unwinds the stack to the innermost restart with a given name and evaluates it */
+
+        let (name, restart_args) = match args.split_first() {
+            Some((name, restart_args)) if name.name() != "" => (name.name(), restart_args.to_vec()),
+            _ => return new_error!("Liszp: expected syntax (invoke-restart <name> <args>..)").into()
+        };
+
+        for (target, frame) in stack.iter().enumerate().rev() {
+            if let Frame::Restarts { restarts, env } = frame {
+                if let Some(restart) = restarts.iter().find(|r| r.name == name) {
+                    let frame = Environment::new_frame(env);
+                    let pending_defaults = restart.signature.bind(restart_args, &frame)?;
+
+                    return Ok(Control::Unwind(Unwinding::Restart {
+                        target,
+                        frame,
+                        pending_defaults,
+                        body: restart.body.clone()
+                    }));
+                }
+            }
+        }
+
+        new_error!("no restart named '{}' is active", name).into()
+    }
+
+
+    fn compute_restarts(args: &[Rc<Value>], stack: &[Frame]) -> Result<Rc<Value>, Error> {
+        /* Returns a list of the names of the active restarts, innermost first */
+
+        if !args.is_empty() {
+            return new_error!("Liszp: function 'compute-restarts' takes no arguments").into();
+        }
+
+        let names = Self::active_restarts(stack).into_iter()
+                                                .map(|(name, _)| Value::Name(name).rc())
+                                                .collect();
+
+        Ok(Value::cons_list(&names))
+    }
+
+
+    /* Continuations */
+
+
+    fn call_with_current_continuation(&mut self, args: Vec<Rc<Value>>, stack: &mut Vec<Frame>) -> Result<Control, Error> {
+       /* Calls a function with the continuation of the call/cc expression
+        *
+        * The continuation is a copy of the stack, so it can be called any
+        * number of times, including after the call/cc expression has returned.
+        */
+
+        let function = match args.as_slice() {
+            [function] => function.clone(),
+            _ => return new_error!("Liszp: function 'call/cc' takes exactly one argument").into()
+        };
+
+        let continuation = Value::Continuation(Rc::new(Continuation {
+            frames: stack.clone(),
+            dynamic_bindings: self.dynamic_bindings.clone(),
+            delimited: false
+        }));
+
+        self.apply(Callee::Function(function), vec![ continuation.rc() ], stack)
+    }
+
+
+    fn eval_reset(args: Vec<Rc<Value>>, env: &Rc<Environment>, stack: &mut Vec<Frame>) -> Control {
+        /* Evaluates the body of a reset expression, delimiting the continuations captured by shift */
+
+        stack.push(Frame::Reset);
+
+        Self::eval_sequence(args, env, stack)
+    }
+
+
+    fn eval_shift(&mut self, args: Vec<Rc<Value>>, env: &Rc<Environment>, stack: &mut Vec<Frame>) -> Result<Control, Error> {
+       /* Evaluates a shift expression
+        *
+        * A shift expression has the form
+        *
+        *   (shift <name> <body>..)
+        *
+        * and removes the stack up to the innermost reset expression, binding
+        * it as a continuation to name while the body is evaluated. The value
+        * of the body is returned from the reset expression.
+        */
+
+        let (name, body) = match args.split_first() {
+            Some((name, body)) if name.name() != "" => (name.name(), body.to_vec()),
+            _ => return new_error!("Liszp: expected syntax (shift <name> <body>..)").into()
+        };
+
+        let reset_index = match stack.iter().rposition(|frame| matches!(frame, Frame::Reset)) {
+            Some(i) => i,
+            None => return new_error!("Liszp: shift expression used outside of a reset expression").into()
+        };
+
+        let frames = stack.split_off(reset_index + 1);
+
+        Self::abandon_generators(&frames);
+        self.leave_frames(&frames);
+
+        let continuation = Value::Continuation(Rc::new(Continuation {
+            frames,
+            dynamic_bindings: None,
+            delimited: true
+        }));
+
+        let shift_env = Environment::new_frame(env);
+
+        shift_env.define(name, continuation.rc());
+
+        Ok(Self::eval_sequence(body, &shift_env, stack))
+    }
+
+
+    fn resume_continuation(&mut self, continuation: &Rc<Continuation>, args: Vec<Rc<Value>>, stack: &mut Vec<Frame>) -> Result<Control, Error> {
+       /* Returns a value to a continuation
+        *
+        * A delimited continuation returns to the caller once its stack has
+        * been evaluated, whereas calling any other continuation abandons the
+        * current stack without evaluating its finally clauses. Generators
+        * running in the abandoned stack are finished, and those running in
+        * the continuation's stack are running again.
+        */
+
+        let value = match args.as_slice() {
+            [] => Value::Nil.rc(),
+            [value] => value.clone(),
+            _ => return new_error!("continuation expected at most 1 argument but received {}", args.len()).into()
+        };
+
+        if continuation.delimited {
+            stack.push(Frame::Reset);
+            self.enter_frames(continuation.frames.iter().cloned(), stack);
+        } else {
+            let abandoned = std::mem::replace(stack, continuation.frames.clone());
+
+            Self::abandon_generators(&abandoned);
+            self.dynamic_bindings = continuation.dynamic_bindings.clone();
+        }
+
+        for frame in continuation.frames.iter() {
+            if let Frame::Generator { generator } = frame {
+                generator.state.replace(GeneratorState::Running);
+            }
+        }
+
+        Ok(Control::Return(value))
+    }
+
+
+    fn abandon_generators(frames: &[Frame]) {
+        /* Finishes the generators running in frames which are being removed from the stack */
+
+        for frame in frames.iter() {
+            if let Frame::Generator { generator } = frame {
+                generator.state.replace(GeneratorState::Done);
+            }
+        }
+    }
+
+
+    /* Generic functions */
+
+
+    fn eval_defgeneric(&mut self, args: &Vec<Rc<Value>>, env: &Rc<Environment>) -> Result<Control, Error> {
+       /* Evaluates a defgeneric expression
+        *
+        * A defgeneric expression has the form
+        *
+        *   (defgeneric <name> <params> <body>..)
+        *
+        * and defines a generic function with no methods, or with the body as
+        * a method accepting any arguments if there is one. Defining a generic
+        * function with the name of a builtin lets methods extend the builtin,
+        * which is still called when no method applies.
+        */
+
+        let (name, params, body) = match args.as_slice() {
+            [name, params, body @ ..] if name.name() != "" => (name.name(), params, body),
+            _ => return new_error!("Liszp: expected syntax (defgeneric <name> <params> <body>..)").into()
+        };
+
+        let generic = self.new_generic(&name);
+
+        if !body.is_empty() {
+            generic.add_method(Self::make_method(&name, params, body, env)?);
+        }
+
+        Ok(Control::Return(Value::Nil.rc()))
+    }
+
+
+    fn eval_defmethod(&mut self, args: &Vec<Rc<Value>>, env: &Rc<Environment>) -> Result<Control, Error> {
+       /* Evaluates a defmethod expression
+        *
+        * A defmethod expression has the form
+        *
+        *   (defmethod <name> (<param> | (<param> : <type>)..) <body>..)
+        *
+        * and adds a method to the generic function name, defining the generic
+        * function first if it doesn't exist.
+        */
+
+        let (name, params, body) = match args.as_slice() {
+            [name, params, body @ ..] if name.name() != "" && !body.is_empty() => (name.name(), params, body),
+            _ => return new_error!("Liszp: expected syntax (defmethod <name> <params> <body>..)").into()
+        };
+
+        let generic = match self.globals.get(&name).as_deref() {
+            Some(Value::Generic(generic)) => generic.clone(),
+            None => self.new_generic(&name),
+            Some(_) => return new_error!("Liszp: cannot define a method of '{}', which is not a generic function", name).into()
+        };
+
+        generic.add_method(Self::make_method(&name, params, body, env)?);
+
+        Ok(Control::Return(Value::Nil.rc()))
+    }
+
+
+    fn new_generic(&mut self, name: &String) -> Rc<GenericFunction> {
+        /* Defines a generic function with no methods in self.globals */
+
+        let is_builtin = Self::is_builtin(name);
+        let generic = GenericFunction::new(name, is_builtin);
+
+        if is_builtin {
+            self.extended_builtins.insert(name.clone());
+        }
+
+        self.globals.define(name, Value::Generic(generic.clone()).rc());
+
+        generic
+    }
+
+
+    fn make_method(name: &str, params: &Rc<Value>, body: &[Rc<Value>], env: &Rc<Environment>) -> Result<Method, Error> {
+        /* Creates a method of a generic function out of a parameter list and a body */
+
+        let (stripped_params, specializers) = GenericFunction::parse_params(params)?;
+
+        let function = Value::Lambda {
+            signature: Rc::new(Signature::parse(&stripped_params)?),
+            body: Self::sequence(body),
+            env: env.clone(),
+            name: Some(name.to_string())
+        };
+
+        Ok(Method { specializers, params: stripped_params, function: function.rc() })
+    }
+
+
+    pub fn display_value(&mut self, value: &Rc<Value>) -> Result<String, Error> {
+       /* Displays a value, using methods of repr for any part of it they apply to
+        *
+        * A method of repr returns the value to display in place of its
+        * argument, and the parts of that value are displayed in the same way.
+        */
+
+        if let Some(Value::Generic(repr)) = self.globals.get("repr").as_deref() {
+            if repr.has_method_for(value) {
+                if let Some(method) = repr.select(std::slice::from_ref(value))? {
+                    let replacement = self.call_function(&method, vec![ value.clone() ])?;
+
+                    return self.display_parts(&replacement);
+                }
+            }
+        }
+
+        self.display_parts(value)
+    }
+
+
+    fn display_parts(&mut self, value: &Rc<Value>) -> Result<String, Error> {
+        /* Displays a value, displaying the elements of lists and fields of structs with display_value */
+
+        match &**value {
+            Value::Cons { .. } => {
+                let mut components = vec![];
+                let mut cursor = value.clone();
+
+                while let Some((car, cdr)) = cursor.uncons() {
+                    components.push(self.display_value(&car)?);
+                    cursor = cdr;
+                }
+
+                if *cursor != Value::Nil {
+                    components.push(format!(". {}", self.display_value(&cursor)?));
+                }
+
+                Ok(format!("({})", components.join(" ")))
+            },
+
+            Value::Struct { kind, fields } if !fields.is_empty() => {
+                let mut components = vec![ kind.constructor_name() ];
+
+                for (field, value) in kind.fields.iter().zip(fields.iter()) {
+                    let value = self.display_value(&value.borrow())?;
+
+                    if kind.sum.is_some() {
+                        components.push(value);
+                    } else {
+                        components.push(format!(":{} {}", field, value));
+                    }
+                }
+
+                Ok(format!("({})", components.join(" ")))
+            },
+
+            _ => Ok(value.to_string())
+        }
+    }
+
+
+    /* Modules */
+
+
+    fn eval_module(&mut self, args: &Vec<Rc<Value>>) -> Result<Control, Error> {
+        /* Evaluates a module expression, declaring the name and exports of the file being loaded */
+
+        let declaration = ModuleDeclaration::parse(args)?;
+
+        if self.module_declaration.is_some() {
+            return new_error!("module '{}' was declared more than once", declaration.name).into();
+        }
+
+        self.module_declaration = Some(declaration);
+
+        Ok(Control::Return(Value::Nil.rc()))
+    }
+
+
+    fn eval_import(&mut self, args: &[Rc<Value>]) -> Result<Control, Error> {
+       /* Evaluates an import expression
+        *
+        * The module's exports are bound as <module>/<name> in the current
+        * namespace, where <module> is the module's name or the alias given
+        * with :as, and any exports listed are bound without the prefix too.
+        * The names are bound to the module's variables rather than to their
+        * values, so a module changing a variable it exports with set! is seen
+        * by the files importing it.
+        */
+
+        let import = Import::parse(args)?;
+        let module = self.load_module(&import.path)?;
+        let prefix = import.alias.as_ref().unwrap_or(&module.name);
+
+        for name in import.names.iter().map(|(name, _)| name) {
+            if !module.exports.contains(name) {
+                return new_error!("module '{}' does not export '{}'", module.name, name).into();
+            }
+        }
+
+        for name in module.exports.iter() {
+            self.globals.define_import(&format!("{}/{}", prefix, name), &module.namespace, name);
+        }
+
+        for (name, new_name) in import.names.iter() {
+            self.globals.define_import(new_name, &module.namespace, name);
+        }
+
+        Ok(Control::Return(Value::Nil.rc()))
+    }
+
+
+    fn load_module(&mut self, path: &String) -> Result<Rc<Module>, Error> {
+       /* Loads a module, or returns it if it has been loaded already
+        *
+        * The path is looked for relative to the directory of the file that
+        * imports it and then on the search path. The module is evaluated in
+        * a namespace of its own, which sees the standard library but not the
+        * definitions of the importing file. The same goes for macros, which
+        * are expanded before a file's expressions are evaluated and so can't
+        * be imported: those a module defines are only used within it.
+        */
+
+        let relative_to = match self.loading.last().and_then(|file| file.parent()) {
+            Some(dir) => dir.to_path_buf(),
+            None => PathBuf::from(".")
+        };
+
+        let search_path = module::search_path(&self.project_root());
+
+        let full_path = match module::resolve(path, &relative_to, &search_path) {
+            Some(full_path) => full_path,
+            None => return new_error!("cannot find module '{}' in {}", path, Self::display_paths(&search_path)).into()
+        };
+
+        if let Some(module) = self.modules.get(&full_path) {
+            return Ok(module.clone());
+        }
+
+        if let Some(i) = self.loading.iter().position(|file| *file == full_path) {
+            let cycle: Vec<String> = self.loading[i..].iter()
+                                                      .chain(std::iter::once(&full_path))
+                                                      .map(|file| file.display().to_string())
+                                                      .collect();
+
+            return new_error!("cyclic import: {}", cycle.join(" -> ")).into();
+        }
+
+        self.check_package(&full_path, &search_path)?;
+
+        let namespace = Environment::new_frame(&self.prelude);
+        let importer = std::mem::replace(&mut self.globals, namespace.clone());
+        let importer_macros = std::mem::replace(&mut self.macros, self.prelude_macros.clone());
+        let importer_declaration = self.module_declaration.take();
+
+        let result = self.eval_file(full_path.display().to_string(), false);
+
+        self.globals = importer;
+        self.macros = importer_macros;
+        let declaration = std::mem::replace(&mut self.module_declaration, importer_declaration);
+
+        result?;
+
+        // a file without a module expression exports everything it defines, and is named after the file
+        let (module_name, export_names) = match declaration {
+            Some(ModuleDeclaration { name, exports }) => (name, exports),
+
+            None => {
+                let name = full_path.file_stem().map_or(path.clone(), |stem| stem.to_string_lossy().to_string());
+
+                (name, namespace.names())
+            }
+        };
+
+        if let Some(name) = export_names.iter().find(|name| namespace.get(name).is_none()) {
+            return new_error!("module '{}' exports '{}', which it does not define", module_name, name).into();
+        }
+
+        let module = Rc::new(Module { name: module_name, namespace, exports: export_names });
+
+        self.modules.insert(full_path, module.clone());
+
+        Ok(module)
+    }
+
+
+    fn check_package(&mut self, file: &Path, search_path: &[PathBuf]) -> Result<(), Error> {
+        /* Checks that a module in a package is listed in its manifest, and that the package's dependencies can be found */
+
+        let package = match file.parent() {
+            Some(dir) => match self.load_package(dir)? {
+                Some(package) => package,
+                None => return Ok(())
+            },
+
+            None => return Ok(())
+        };
+
+        let module_name = file.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+
+        if !package.modules.contains(&module_name) {
+            return new_error!("package '{}' has no module '{}', its modules are: {}", package.name, module_name, package.modules.join(", ")).into();
+        }
+
+        match package.dependencies.iter().find(|dependency| module::find_package(dependency, search_path).is_none()) {
+            Some(dependency) => new_error!("package '{}' depends on '{}', which is not in {}", package.name, dependency, Self::display_paths(search_path)).into(),
+            None => Ok(())
+        }
+    }
+
+
+    fn load_package(&mut self, dir: &Path) -> Result<Option<Package>, Error> {
+        /* Reads the manifest of the package in a directory, if it is one */
+
+        let manifest_path = dir.join(module::MANIFEST_FILENAME);
+
+        let source = match std::fs::read_to_string(&manifest_path) {
+            Ok(source) => source,
+            Err(_) => return Ok(None)
+        };
+
+        let filename = manifest_path.display().to_string();
+        let manifest = read::read(&source, &filename, false, self)?;
+
+        Package::parse(&manifest).map(Some).map_err(|e| e.add_filename(&filename))
+    }
+
+
+    pub fn list_modules(&mut self, project_root: &Path) -> Result<Vec<(String, PathBuf, String)>, Error> {
+       /* Lists the modules which can be imported from a project by name
+        *
+        * returns
+        * -------
+        * the name each module is imported by, its file, and a description of
+        * where it comes from, with modules shadowed by earlier directories of
+        * the search path left out
+        */
+
+        let search_path = module::search_path(project_root);
+        let stdlib = module::stdlib_dir();
+        let mut listed: Vec<(String, PathBuf, String)> = vec![];
+
+        for dir in search_path.iter() {
+            let mut entries: Vec<PathBuf> = match std::fs::read_dir(dir) {
+                Ok(entries) => entries.filter_map(|entry| entry.ok().map(|e| e.path())).collect(),
+                Err(_) => continue
+            };
+
+            entries.sort();
+
+            let mut found = vec![];
+
+            for entry in entries.iter() {
+                let stem = entry.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+
+                let is_prelude = *dir == stdlib && module::PRELUDE_FILENAMES.iter().any(|filename| entry.ends_with(filename));
+
+                if is_prelude {
+                    // the standard library's prelude is loaded before every program rather than imported
+                    continue;
+                } else if entry.is_file() && entry.extension().is_some_and(|ext| ext == "lzp") {
+                    found.push((stem, entry.clone(), format!("in {}", dir.display())));
+                } else if let Some(package) = self.load_package(entry)? {
+                    let mut description = format!("package {}", package.name);
+
+                    if let Some(version) = package.version.as_ref() {
+                        description = format!("{} {}", description, version);
+                    }
+
+                    for dependency in package.dependencies.iter() {
+                        if module::find_package(dependency, &search_path).is_none() {
+                            description = format!("{}, missing dependency '{}'", description, dependency);
+                        }
+                    }
+
+                    for module_name in package.modules.iter() {
+                        found.push((format!("{}/{}", stem, module_name), entry.join(format!("{}.lzp", module_name)), description.clone()));
+                    }
+                }
+            }
+
+            for (name, file, description) in found.into_iter() {
+                if !listed.iter().any(|(n, _, _)| *n == name) {
+                    listed.push((name, file, description));
+                }
+            }
+        }
+
+        Ok(listed)
+    }
+
+
+    fn project_root(&self) -> PathBuf {
+        /* Returns the directory of the program being run, whose lib directory is searched for modules */
+
+        match self.loading.iter().find(|file| !file.starts_with(module::stdlib_dir())).and_then(|file| file.parent()) {
+            Some(dir) => dir.to_path_buf(),
+            None => PathBuf::from(".")
+        }
+    }
+
+
+    fn display_paths(paths: &[PathBuf]) -> String {
+        /* Displays a search path for error messages */
+
+        let paths: Vec<String> = paths.iter().map(|path| format!("'{}'", path.display())).collect();
+
+        paths.join(", ")
+    }
+
+
+    /* Parameters */
+
+
+    fn eval_parameterize(&mut self, args: Vec<Rc<Value>>, env: &Rc<Environment>, stack: &mut Vec<Frame>) -> Result<Control, Error> {
+       /* Evaluates a parameterize expression
+        *
+        * A parameterize expression has the form
+        *
+        *   (parameterize ([<parameter> <value>]..) <body>..)
+        *
+        * and evaluates the body with each parameter returning the new value
+        * instead of its own. The new values only last for the dynamic extent
+        * of the body, so they are undone however it is left, whether by
+        * returning, by an error or by a continuation.
+        */
+
+        let (bindings, body) = match args.split_first() {
+            Some((bindings, body)) if !body.is_empty() => (bindings, body),
+            _ => return new_error!("Liszp: expected syntax (parameterize ([<parameter> <value>]..) <body>..)").into()
+        };
+
+        let mut exprs = vec![];
+
+        for binding in bindings.to_list().unwrap_or_else(|| vec![ bindings.clone() ]).iter() {
+            match binding.to_list().as_deref() {
+                Some([parameter, value]) => exprs.extend([ parameter.clone(), value.clone() ]),
+                _ => return new_error!("Liszp: expected binding [<parameter> <value>] in parameterize expression, received '{}'", binding).into()
+            }
+        }
+
+        let callee = Callee::Parameterize { body: body.to_vec(), env: env.clone() };
+
+        self.eval_args(callee, exprs, env, stack)
+    }
+
+
+    fn parameter_value(&self, parameter: &Rc<Parameter>, args: Vec<Rc<Value>>) -> Result<Control, Error> {
+        /* Returns the value of a parameter given by the innermost parameterize expression rebinding it */
+
+        if !args.is_empty() {
+            return new_error!("parameter expected no arguments but received {}", args.len()).into();
+        }
+
+        let mut binding = &self.dynamic_bindings;
+
+        while let Some(b) = binding {
+            if Rc::ptr_eq(&b.parameter, parameter) {
+                return Ok(Control::Return(b.value.clone()));
+            }
+
+            binding = &b.outer;
+        }
+
+        Ok(Control::Return(parameter.value.clone()))
+    }
+
+
+    fn enter_frames<I: IntoIterator<Item = Frame>>(&mut self, frames: I, stack: &mut Vec<Frame>) {
+       /* Pushes frames onto the stack, rebinding the parameters of any parameterize frames among them
+        *
+        * The dynamic bindings are kept in self.dynamic_bindings rather than
+        * being looked up in the stack, so that reading a parameter doesn't
+        * depend on the depth of the stack. Each parameterize frame remembers
+        * the bindings it was pushed with, to restore them when it is removed.
+        */
+
+        for mut frame in frames {
+            if let Frame::Parameterize { bindings, outer } = &mut frame {
+                *outer = self.dynamic_bindings.clone();
+
+                for (parameter, value) in bindings.iter() {
+                    self.dynamic_bindings = Some(Rc::new(DynamicBinding {
+                        parameter: parameter.clone(),
+                        value: value.clone(),
+                        outer: self.dynamic_bindings.take()
+                    }));
+                }
+            }
+
+            stack.push(frame);
+        }
+    }
+
+
+    fn leave_frames(&mut self, frames: &[Frame]) {
+        /* Restores the dynamic bindings from before frames which have been removed from the top of the stack */
+
+        let outermost = frames.iter().find_map(|frame| match frame {
+            Frame::Parameterize { outer, .. } => Some(outer),
+            _ => None
+        });
+
+        if let Some(outer) = outermost {
+            self.dynamic_bindings = outer.clone();
+        }
+    }
+
+
+    /* Promises */
+
+
+    fn eval_delay(args: &Vec<Rc<Value>>, env: &Rc<Environment>) -> Result<Control, Error> {
+       /* Evaluates a delay expression
+        *
+        * A delay expression has the form
+        *
+        *   (delay <expr>)
+        *
+        * and evaluates to a promise, which evaluates the expression the first
+        * time it is forced and returns the same value whenever it is forced
+        * again.
+        */
+
+        match args.as_slice() {
+            [expr] => {
+                let promise = Promise {
+                    state: RefCell::new(PromiseState::Delayed {
+                        expr: expr.clone(),
+                        env: env.clone()
+                    })
+                };
+
+                Ok(Control::Return(Value::Promise(Rc::new(promise)).rc()))
+            },
+
+            _ => new_error!("Liszp: delay expression has syntax (delay <expr>)").into()
+        }
+    }
+
+
+    fn force(args: Vec<Rc<Value>>, stack: &mut Vec<Frame>) -> Result<Control, Error> {
+        /* Returns the value of a promise, evaluating its expression if it hasn't been forced yet */
+
+        let promise = match args.as_slice() {
+            [value] => {
+                match &**value {
+                    Value::Promise(promise) => promise.clone(),
+
+                    // forcing a value which isn't a promise returns it unchanged
+                    _ => return Ok(Control::Return(value.clone()))
+                }
+            },
+
+            _ => return new_error!("Liszp: function 'force' takes exactly one argument").into()
+        };
+
+        let state = promise.state.borrow();
+
+        match &*state {
+            PromiseState::Forced(value) => Ok(Control::Return(value.clone())),
+
+            PromiseState::Delayed { expr, env } => {
+                let control = Control::Eval(expr.clone(), env.clone());
+
+                drop(state);
+                stack.push(Frame::Force { promise });
+
+                Ok(control)
+            }
+        }
+    }
+
+
+    /* Generators */
+
+
+    fn make_generator(args: &[Rc<Value>], env: &Rc<Environment>) -> Control {
+       /* Evaluates a generator expression
+        *
+        * A generator expression has the form
+        *
+        *   (generator <body>..)
+        *
+        * and evaluates to a generator, which evaluates its body up to the next
+        * yield each time it is called and returns the yielded value. Once the
+        * body has finished, calling the generator returns nil.
+        */
+
+        let generator = Generator {
+            state: RefCell::new(GeneratorState::Ready {
+                body: Self::sequence(args),
+                env: env.clone()
+            })
+        };
+
+        Control::Return(Value::Generator(Rc::new(generator)).rc())
+    }
+
+
+    fn resume_generator(&mut self, generator: &Rc<Generator>, args: Vec<Rc<Value>>, stack: &mut Vec<Frame>) -> Result<Control, Error> {
+        /* Continues a generator from where it last yielded, sending it an optional value */
+
+        let value = match args.as_slice() {
+            [] => Value::Nil.rc(),
+            [value] => value.clone(),
+            _ => return new_error!("generator expected at most 1 argument but received {}", args.len()).into()
+        };
+
+        let state = generator.state.replace(GeneratorState::Running);
+
+        match state {
+            GeneratorState::Ready { body, env } => {
+                stack.push(Frame::Generator { generator: generator.clone() });
+
+                Ok(Control::Eval(body, env))
+            },
+
+            GeneratorState::Suspended { frames } => {
+                stack.push(Frame::Generator { generator: generator.clone() });
+                self.enter_frames(frames, stack);
+
+                Ok(Control::Return(value))
+            },
+
+            GeneratorState::Running => new_error!("generator was called while it was already running").into(),
+
+            GeneratorState::Done => {
+                generator.state.replace(GeneratorState::Done);
+
+                Ok(Control::Return(Value::Nil.rc()))
+            }
+        }
+    }
+
+
+    fn yield_value(&mut self, args: Vec<Rc<Value>>, stack: &mut Vec<Frame>) -> Result<Control, Error> {
+        /* Suspends the innermost running generator, returning a value from the call that resumed it */
+
+        let value = match args.as_slice() {
+            [] => Value::Nil.rc(),
+            [value] => value.clone(),
+            _ => return new_error!("Liszp: function 'yield' takes at most one argument").into()
+        };
+
+        let generator_index = match stack.iter().rposition(|frame| matches!(frame, Frame::Generator { .. })) {
+            Some(i) => i,
+            None => return new_error!("Liszp: yield used outside of a generator").into()
+        };
+
+        let frames = stack.split_off(generator_index + 1);
+
+        self.leave_frames(&frames);
+
+        if let Some(Frame::Generator { generator }) = stack.pop() {
+            generator.state.replace(GeneratorState::Suspended { frames });
+        }
+
+        Ok(Control::Return(value))
+    }
+
+
+    /* function evaluation */
+
+
+    fn is_extended_builtin(&self, name: &String, env: &Rc<Environment>) -> bool {
+       /* Returns whether a builtin function has been extended with methods where env can see them
+        *
+        * A generic function extending a builtin is defined in the namespace of
+        * the file that added its methods, so code in other namespaces, such as
+        * the standard library, keeps calling the builtin itself.
+        */
+
+        self.extended_builtins.contains(name) && matches!(env.get(name).as_deref(), Some(Value::Generic(_)))
+    }
+
+
+    pub fn is_builtin(name: &str) -> bool {
+        /* Returns whether a name refers to a builtin function */
+
+        matches!(name,
+            "append" | "bool?" | "box" | "box?" | "car" | "cdr" | "cons" | "cons?" | "equals?" | "error"
+            | "error?" | "error-message" | "error-payload" | "float?" | "generator?" | "gensym"
+            | "force" | "generator-done?" | "int?" | "list" | "macroexpand" | "macroexpand-1"
+            | "make-parameter" | "make-promise" | "name?" | "nil?" | "panic" | "print" | "println" | "promise?"
+            | "raise" | "set-box!"
+            | "repr" | "set-car!" | "set-cdr!" | "set-dispatch-macro!" | "str?" | "type-of" | "unbox"
+            | "call/cc" | "compute-restarts" | "invoke-restart" | "signal" | "yield"
+            | "+" | "-" | "*" | "/" | "%" | "and" | "or" | "xor" | "not"
+            | "<" | ">" | "<=" | ">=" | "==" | "!="
+        )
+    }
+
+
+    fn apply_builtin(name: &String, args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
+        /* Calls a builtin function on its evaluated arguments */
+
+        match name.as_str() {
+            "append"         => builtin::append(args),
+            "bool?"          => builtin::value_is_bool(args),
+            "box"            => builtin::make_box(args),
+            "box?"           => builtin::value_is_box(args),
+            "car"            => builtin::car(args),
+            "cdr"            => builtin::cdr(args),
+            "cons"           => builtin::cons(args),
+            "cons?"          => builtin::value_is_cons(args),
+            "equals?"        => builtin::values_are_equal(args),
+            "error"          => builtin::make_error(args),
+            "error?"         => builtin::value_is_error(args),
+            "error-message"  => builtin::error_message(args),
+            "error-payload"  => builtin::error_payload(args),
+            "float?"         => builtin::value_is_float(args),
+            "generator?"     => builtin::value_is_generator(args),
+            "gensym"         => builtin::gensym(args),
+            "generator-done?" => builtin::generator_is_done(args),
+            "int?"           => builtin::value_is_int(args),
+            "list"           => builtin::make_list(args),
+            "make-parameter" => builtin::make_parameter(args),
+            "make-promise"   => builtin::make_promise(args),
+            "name?"          => builtin::value_is_name(args),
+            "nil?"           => builtin::value_is_nil(args),
+            "panic"          => builtin::panic(args),
+            "print"          => builtin::print_value(args, false),
+            "println"        => builtin::print_value(args, true),
+            "promise?"       => builtin::value_is_promise(args),
+            "raise"          => builtin::raise(args),
+            "repr"           => builtin::repr(args),
+            "set-box!"       => builtin::set_box(args),
+            "set-car!"       => builtin::set_car(args),
+            "set-cdr!"       => builtin::set_cdr(args),
+            "str?"           => builtin::value_is_str(args),
+            "type-of"        => builtin::type_of(args),
+            "unbox"          => builtin::unbox(args),
+            "+"|"-"|"*"|"/"  => operators::arithmetic_expression(name, args),
+            "%"              => operators::modulo(args),
+            "and"|"or"|"xor" => operators::logical_operation(name, args),
+            "not"            => operators::logical_negation(args),
+            "<"|">"|"<="
+            |">="|"=="|"!="  => operators::comparison(name, args),
+            _                => unreachable!()
+        }
+    }
+
+
+    fn eval_args(&mut self, callee: Callee, args: Vec<Rc<Value>>, env: &Rc<Environment>, stack: &mut Vec<Frame>) -> Result<Control, Error> {
+        /* Starts evaluating the arguments of a call, left to right */
+
+        let mut remaining = args;
+
+        remaining.reverse();
+
+        match remaining.pop() {
+            Some(first_arg) => {
+                stack.push(Frame::Args {
+                    callee,
+                    evaluated: vec![],
+                    remaining,
+                    env: env.clone()
+                });
+
+                Ok(Control::Eval(first_arg, env.clone()))
+            },
+
+            None => self.apply(callee, vec![], stack)
+        }
+    }
+
+
+    fn apply(&mut self, callee: Callee, arg_values: Vec<Rc<Value>>, stack: &mut Vec<Frame>) -> Result<Control, Error> {
+        /* Calls a function on its evaluated arguments */
+
+        let function = match callee {
+            Callee::Builtin(name) => {
+                // these builtins need to inspect the stack
+                return match name.as_str() {
+                    "call/cc"          => self.call_with_current_continuation(arg_values, stack),
+                    "compute-restarts" => Ok(Control::Return(Self::compute_restarts(&arg_values, stack)?)),
+                    "force"            => Self::force(arg_values, stack),
+                    "invoke-restart"   => Self::invoke_restart(arg_values, stack),
+                    "macroexpand" | "macroexpand-1" => Ok(Control::Return(self.macroexpand(&name, &arg_values)?)),
+                    "set-dispatch-macro!" => Ok(Control::Return(self.set_dispatch_macro(&arg_values)?)),
+                    "signal"           => {
+                        match arg_values.as_slice() {
+                            [condition] => self.signal(condition.clone(), None, stack),
+                            _ => new_error!("Liszp: function 'signal' takes exactly one argument").into()
+                        }
+                    },
+                    "yield"            => self.yield_value(arg_values, stack),
+                    "print" | "println" if self.extended_builtins.contains("repr") => {
+                        match arg_values.as_slice() {
+                            [value] => {
+                                let string = self.display_value(value)?;
+
+                                if name == "println" {
+                                    println!("{}", string);
+                                } else {
+                                    print!("{}", string);
+                                }
+
+                                Ok(Control::Return(value.clone()))
+                            },
+
+                            _ => new_error!("Function {} takes 1 argument only", name).into()
+                        }
+                    },
+                    _ => Ok(Control::Return(Self::apply_builtin(&name, &arg_values)?))
+                };
+            },
+
+            Callee::Function(f) => f,
+
+            Callee::HandlerBind { body, env } => {
+                // handlers are called like any other function, so anything callable with one argument will do
+                if let Some(handler) = arg_values.iter().find(|v| v.type_name() != "function" && !matches!(&***v, Value::Continuation(_))) {
+                    return new_error!("handler-bind expected functions as handlers, received '{}'", handler).into();
+                }
+
+                stack.push(Frame::Handlers { handlers: arg_values });
+
+                return Ok(Self::eval_sequence(body, &env, stack));
+            },
+
+            Callee::Parameterize { body, env } => {
+                let mut bindings = vec![];
+
+                for pair in arg_values.chunks(2) {
+                    match &*pair[0] {
+                        Value::Parameter(parameter) => bindings.push((parameter.clone(), pair[1].clone())),
+                        _ => return new_error!("parameterize expected a parameter, received '{}'", pair[0]).into()
+                    }
+                }
+
+                self.enter_frames([ Frame::Parameterize { bindings, outer: None } ], stack);
+
+                return Ok(Self::eval_sequence(body, &env, stack));
+            }
+        };
+
+        match &*function {
+            Value::Lambda { signature, body, env, name } => {
+                // a call in tail position replaces the frame of the function it is returning from
+                if let Some(Frame::Call { .. }) = stack.last() {
+                    stack.pop();
+                }
+
+                stack.push(Frame::Call { name: name.clone() });
+
+                let frame = Environment::new_frame(env);
+                let pending_defaults = signature.bind(arg_values, &frame)?;
+
+                Ok(Self::eval_defaults(pending_defaults, frame, body.clone(), stack))
+            },
+
+            Value::Continuation(continuation) => self.resume_continuation(continuation, arg_values, stack),
+
+            Value::Generator(generator) => self.resume_generator(generator, arg_values, stack),
+
+            Value::Parameter(parameter) => self.parameter_value(parameter, arg_values),
+
+            Value::Generic(generic) => {
+                match generic.select(&arg_values)? {
+                    Some(method) => self.apply(Callee::Function(method), arg_values, stack),
+                    None => self.apply(Callee::Builtin(generic.name.clone()), arg_values, stack)
+                }
+            },
+
+            Value::StructFunction { kind, operation, name } => Ok(Control::Return(kind.apply(*operation, name, &arg_values)?)),
+
+            _ => new_error!("expected function, received '{}'", function).into()
+        }
+    }
+
+
+    fn eval_defaults(pending_defaults: Vec<(String, Rc<Value>)>, frame: Rc<Environment>, body: Rc<Value>, stack: &mut Vec<Frame>) -> Control {
+        /* Evaluates the defaults of parameters that weren't supplied, in order, and then the body */
+
+        let mut pending = pending_defaults;
+
+        if pending.is_empty() {
+            return Control::Eval(body, frame);
+        }
+
+        let (name, default) = pending.remove(0);
+
+        stack.push(Frame::Default {
+            name,
+            pending,
+            frame: frame.clone(),
+            body
+        });
+
+        Control::Eval(default, frame)
+    }
 }
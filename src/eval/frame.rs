@@ -0,0 +1,321 @@
+use crate::error::Error;
+use crate::eval::{ Environment, Signature };
+use crate::eval::pattern::Clause;
+use crate::value::Value;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+
+#[derive(Clone)]
+pub enum Control {
+    /* What the evaluator does next: evaluate an expression or return a value to the stack */
+
+    Eval(Rc<Value>, Rc<Environment>),
+
+    Return(Rc<Value>),
+
+    Unwind(Unwinding)
+}
+
+
+#[derive(Clone)]
+pub enum Unwinding {
+    /* Why the stack is being unwound */
+
+    // an error that no handler dealt with
+    Error(Error),
+
+    // a restart was invoked, and the frame at index target in the stack established it
+    Restart {
+        target: usize,
+        frame: Rc<Environment>,
+        pending_defaults: Vec<(String, Rc<Value>)>,
+        body: Rc<Value>
+    }
+}
+
+
+#[derive(Clone)]
+pub enum Callee {
+    /* The function a call's arguments are being evaluated for */
+
+    Builtin(String),
+
+    Function(Rc<Value>),
+
+    // the arguments are the handlers of a handler-bind expression
+    HandlerBind {
+        body: Vec<Rc<Value>>,
+        env: Rc<Environment>
+    },
+
+    // the arguments are the parameters and values of a parameterize expression, in pairs
+    Parameterize {
+        body: Vec<Rc<Value>>,
+        env: Rc<Environment>
+    }
+}
+
+
+#[derive(Clone)]
+pub struct Catch {
+    /* The catch clause of a try expression */
+
+    pub name: String,
+    pub handler: Vec<Rc<Value>>
+}
+
+
+#[derive(Clone)]
+pub struct Restart {
+    /* A restart established by a restart-case expression */
+
+    pub name: String,
+    pub signature: Rc<Signature>,
+    pub body: Rc<Value>
+}
+
+
+pub struct Continuation {
+    /* The stack captured by call/cc or shift, which a continuation value reinstates */
+
+    pub frames: Vec<Frame>,
+
+    // the parameters rebound when the continuation was captured by call/cc
+    pub dynamic_bindings: Option<Rc<DynamicBinding>>,
+
+    // delimited continuations are composed with the stack they are called
+    // from rather than replacing it
+    pub delimited: bool
+}
+
+
+pub struct DynamicBinding {
+    /* A parameter's value given by a parameterize expression, in front of the bindings it was made within */
+
+    pub parameter: Rc<Parameter>,
+    pub value: Rc<Value>,
+    pub outer: Option<Rc<DynamicBinding>>
+}
+
+
+pub struct Generator {
+    /* A resumable computation created by a generator expression */
+
+    pub state: RefCell<GeneratorState>
+}
+
+
+pub struct Parameter {
+    /* A dynamic variable created by make-parameter, whose value parameterize can rebind */
+
+    pub value: Rc<Value>
+}
+
+
+pub struct Promise {
+    /* A value computed on demand by a delay expression, and remembered once forced */
+
+    pub state: RefCell<PromiseState>
+}
+
+
+pub enum PromiseState {
+    /* Whether a promise's value has been computed */
+
+    // the expression hasn't finished being evaluated
+    Delayed {
+        expr: Rc<Value>,
+        env: Rc<Environment>
+    },
+
+    Forced(Rc<Value>)
+}
+
+
+pub enum GeneratorState {
+    /* How far a generator has got through its body */
+
+    // the body hasn't started yet
+    Ready {
+        body: Rc<Value>,
+        env: Rc<Environment>
+    },
+
+    // the body is suspended at a yield, and frames is the stack it was suspended with
+    Suspended {
+        frames: Vec<Frame>
+    },
+
+    Running,
+
+    Done
+}
+
+
+#[derive(Clone)]
+pub enum Frame {
+    /* A continuation: the work left to do once the value being computed is returned */
+
+    // receives the value being assigned by a set! expression
+    Assign {
+        name: String,
+        env: Rc<Environment>
+    },
+
+    // receives a function's evaluated arguments one at a time
+    Args {
+        callee: Callee,
+        evaluated: Vec<Rc<Value>>,
+        remaining: Vec<Rc<Value>>,
+        env: Rc<Environment>
+    },
+
+    // marks that the evaluator is inside a function body, for stack traces
+    Call {
+        name: Option<String>
+    },
+
+    // receives the default value of a parameter that wasn't supplied
+    Default {
+        name: String,
+        pending: Vec<(String, Rc<Value>)>,
+        frame: Rc<Environment>,
+        body: Rc<Value>
+    },
+
+    // receives the value being bound by a def expression
+    Define {
+        name: String
+    },
+
+    // receives the value to evaluate in an eval expression
+    Eval {
+        env: Rc<Environment>
+    },
+
+    // receives the value of a catch clause, before its try's finally clause is evaluated
+    Finally {
+        exprs: Vec<Rc<Value>>,
+        env: Rc<Environment>
+    },
+
+    // receives the evaluated function of a call
+    Function {
+        args: Vec<Rc<Value>>,
+        env: Rc<Environment>
+    },
+
+    // marks the dynamic extent of a parameterize expression, in which the parameters have new values
+    //
+    // outer is the evaluator's dynamic bindings when the frame was pushed,
+    // which are restored when it is removed from the stack
+    Parameterize {
+        bindings: Vec<(Rc<Parameter>, Rc<Value>)>,
+        outer: Option<Rc<DynamicBinding>>
+    },
+
+    // receives the value of a promise's expression, to remember it
+    Force {
+        promise: Rc<Promise>
+    },
+
+    // marks the extent of a running generator, and receives the value of its body
+    Generator {
+        generator: Rc<Generator>
+    },
+
+    // receives the value of the guard of a match clause whose pattern matched
+    Guard {
+        value: Rc<Value>,
+        clauses: Rc<Vec<Clause>>,
+        index: usize,
+        env: Rc<Environment>,
+        clause_env: Rc<Environment>
+    },
+
+    // marks the handlers established by a handler-bind expression
+    Handlers {
+        handlers: Vec<Rc<Value>>
+    },
+
+    // receives the condition of an if expression
+    If {
+        true_case: Rc<Value>,
+        false_case: Rc<Value>,
+        env: Rc<Environment>
+    },
+
+    // marks the extent of the continuations captured by shift expressions
+    Reset,
+
+    // receives the value being matched by a match expression
+    Match {
+        clauses: Rc<Vec<Clause>>,
+        env: Rc<Environment>
+    },
+
+    // receives the value of a finally clause, which is discarded in favour of control
+    Resume {
+        control: Control
+    },
+
+    // receives the value of the body of a restart-case expression
+    Restarts {
+        restarts: Vec<Restart>,
+        env: Rc<Environment>
+    },
+
+    // receives the value of an expression in a sequence, which is discarded
+    Sequence {
+        remaining: Vec<Rc<Value>>,
+        env: Rc<Environment>
+    },
+
+    // receives the value of a handler that declined to handle a condition
+    //
+    // handlers lists the handlers left to try, innermost last, with the index
+    // of the frame that established each. While a handler runs, handlers
+    // established at or above its own index are disabled.
+    Signal {
+        condition: Rc<Value>,
+        error: Option<Error>,
+        handlers: Vec<(usize, Rc<Value>)>,
+        handler_index: usize
+    },
+
+    // receives the value of the body of a try expression
+    Try {
+        catch: Option<Catch>,
+        finally: Vec<Rc<Value>>,
+        env: Rc<Environment>
+    }
+}
+
+
+impl std::fmt::Debug for Continuation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<continuation>")
+    }
+}
+
+
+impl std::fmt::Debug for Parameter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<parameter>")
+    }
+}
+
+
+impl std::fmt::Debug for Promise {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<promise>")
+    }
+}
+
+
+impl std::fmt::Debug for Generator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<generator>")
+    }
+}
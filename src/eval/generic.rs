@@ -0,0 +1,219 @@
+use crate::error::Error;
+use crate::new_error;
+use crate::value::Value;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+
+#[derive(Debug)]
+pub struct GenericFunction {
+    /* A function whose implementation is chosen by the types of its arguments */
+
+    pub name: String,
+    pub methods: RefCell<Vec<Method>>,
+
+    // whether calls which no method applies to fall back to the builtin of the same name
+    pub builtin: bool
+}
+
+
+#[derive(Debug)]
+pub struct Method {
+    /* A method of a generic function
+     *
+     * specializers has the type name each required parameter is specialized
+     * on, or None if it accepts any value.
+     */
+
+    pub specializers: Vec<Option<String>>,
+    pub params: Rc<Value>,
+    pub function: Rc<Value>
+}
+
+
+impl GenericFunction {
+
+    /* Instantiation */
+
+    pub fn new(name: &str, builtin: bool) -> Rc<Self> {
+        /* Creates a generic function with no methods */
+
+        Rc::new(GenericFunction {
+            name: name.to_string(),
+            methods: RefCell::new(vec![]),
+            builtin
+        })
+    }
+
+
+    /* Methods */
+
+
+    pub fn parse_params(params: &Rc<Value>) -> Result<(Rc<Value>, Vec<Option<String>>), Error> {
+       /* Parses the parameter list of a defmethod expression
+        *
+        * Required parameters are either as in a lambda expression, accepting
+        * any value, or a list (<name> : <type>) which only accepts values of a
+        * type, where the type is a name returned by type-of or the name of a
+        * variant of a sum type. The rest of the parameter list is as in a
+        * lambda expression.
+        *
+        * returns
+        * -------
+        * the parameter list with the types removed, and the specializers
+        */
+
+        let components = match &**params {
+            Value::Nil => return Ok((params.clone(), vec![])),
+            Value::Cons { .. } => params.to_list().unwrap(),
+            _ => return new_error!("Liszp: expected a list of parameters in defmethod expression").into()
+        };
+
+        let mut stripped = Vec::with_capacity(components.len());
+        let mut specializers = vec![];
+        let mut in_required = true;
+
+        for component in components.iter() {
+            if matches!(component.name().as_str(), "?" | ":" | "@") {
+                in_required = false;
+            }
+
+            if !in_required {
+                stripped.push(component.clone());
+                continue;
+            }
+
+            match component.to_list().as_deref() {
+                Some([name, colon, type_name]) if name.name() != "" && colon.name() == ":" && type_name.name() != "" => {
+                    stripped.push(name.clone());
+                    specializers.push(Some(type_name.name()));
+                },
+
+                _ => {
+                    stripped.push(component.clone());
+                    specializers.push(None);
+                }
+            }
+        }
+
+        Ok((Value::cons_list(&stripped), specializers))
+    }
+
+
+    pub fn add_method(&self, method: Method) {
+        /* Adds a method, replacing any method with the same specializers */
+
+        let mut methods = self.methods.borrow_mut();
+
+        match methods.iter().position(|m| m.specializers == method.specializers) {
+            Some(i) => methods[i] = method,
+            None => methods.push(method)
+        }
+    }
+
+
+    /* Dispatch */
+
+
+    pub fn select(&self, args: &[Rc<Value>]) -> Result<Option<Rc<Value>>, Error> {
+       /* Chooses the most specific method applicable to some arguments
+        *
+        * A method is more specific than another if it is more specific in its
+        * first parameter, or equally specific in it and more specific in the
+        * rest. A parameter specialized on a variant is more specific than one
+        * specialized on its sum type, which is more specific than one which
+        * accepts any value.
+        *
+        * returns
+        * -------
+        * - Ok(Some(function)) for the chosen method
+        * - Ok(None) if no method applies, but the call falls back to a builtin
+        * - Err(error) listing the methods if no method applies
+        */
+
+        let methods = self.methods.borrow();
+
+        let chosen = methods.iter()
+                            .filter_map(|method| method.specificity(args).map(|s| (s, method)))
+                            .max_by(|(a, _), (b, _)| a.cmp(b));
+
+        match chosen {
+            Some((_, method)) => Ok(Some(method.function.clone())),
+
+            None if self.builtin => Ok(None),
+
+            None => {
+                let types: Vec<String> = args.iter().map(|arg| arg.type_name()).collect();
+
+                let candidates: Vec<String> = methods.iter()
+                                                     .map(|m| format!("({} {})", self.name, m.params_with_types()))
+                                                     .collect();
+
+                new_error!(
+                    "no method of '{}' applies to arguments of types ({}), methods are: {}",
+                    self.name,
+                    types.join(" "),
+                    if candidates.is_empty() { "none".into() } else { candidates.join(", ") }
+                ).into()
+            }
+        }
+    }
+
+
+    pub fn has_method_for(&self, value: &Rc<Value>) -> bool {
+        /* Returns whether a method specialized on some type applies to a single value */
+
+        self.methods
+            .borrow()
+            .iter()
+            .any(|m| m.specializers.iter().any(|s| s.is_some()) && m.specificity(std::slice::from_ref(value)).is_some())
+    }
+}
+
+
+impl Method {
+    fn specificity(&self, args: &[Rc<Value>]) -> Option<Vec<u8>> {
+        /* Returns how specific the method is for each argument, or None if it doesn't apply */
+
+        let signature = match &*self.function {
+            Value::Lambda { signature, .. } => signature,
+            _ => return None
+        };
+
+        if args.len() < self.specializers.len() || !signature.accepts(args.len()) {
+            return None;
+        }
+
+        let mut specificity = Vec::with_capacity(self.specializers.len());
+
+        for (specializer, arg) in self.specializers.iter().zip(args.iter()) {
+            let s = match (specializer, &**arg) {
+                (None, _) => 0,
+                (Some(t), Value::Struct { kind, .. }) if kind.sum.is_some() && kind.name == *t => 2,
+                (Some(t), _) if arg.type_name() == *t => 1,
+                _ => return None
+            };
+
+            specificity.push(s);
+        }
+
+        Some(specificity)
+    }
+
+
+    fn params_with_types(&self) -> String {
+        /* Displays the method's parameters with their types */
+
+        let params = self.params.to_list().unwrap_or_default();
+        let mut components = Vec::with_capacity(params.len());
+
+        for (i, param) in params.iter().enumerate() {
+            match self.specializers.get(i) {
+                Some(Some(t)) => components.push(format!("({} : {})", param, t)),
+                _ => components.push(param.to_string())
+            }
+        }
+
+        components.join(" ")
+    }
+}
@@ -1,5 +1,25 @@
 mod builtin;
+mod env;
 mod evaluator;
+mod frame;
+mod generic;
+mod module;
 mod operators;
+mod pattern;
+mod signature;
+mod structs;
+mod syntax;
 
+pub use builtin::fresh_name as fresh_name;
+pub use env::Environment as Environment;
+pub use frame::Continuation as Continuation;
+pub use frame::Generator as Generator;
+pub use generic::GenericFunction as GenericFunction;
+pub use pattern::Pattern as Pattern;
+pub use frame::Parameter as Parameter;
+pub use module::search_path as search_path;
+pub use frame::Promise as Promise;
 pub use evaluator::Evaluator as Evaluator;
+pub use signature::Signature as Signature;
+pub use structs::{ StructOperation, StructType };
+pub use syntax::Syntax as Syntax;
@@ -0,0 +1,273 @@
+use crate::error::Error;
+use crate::eval::Environment;
+use crate::new_error;
+use crate::value::Value;
+use std::path::{ Path, PathBuf };
+use std::rc::Rc;
+
+
+// the name of the file describing a package, in the package's directory
+pub const MANIFEST_FILENAME: &str = "package.lzp";
+
+// the files of the standard library loaded before every program, in order, which aren't modules
+pub const PRELUDE_FILENAMES: [&str; 2] = [ "std-macros.lzp", "std-functions.lzp" ];
+
+
+#[derive(Debug)]
+pub struct Module {
+    /* A source file loaded by an import expression */
+
+    pub name: String,
+
+    // the namespace the module was evaluated in, which holds the variables it exports
+    pub namespace: Rc<Environment>,
+    pub exports: Vec<String>
+}
+
+
+pub struct ModuleDeclaration {
+    /* The name and exports a module gives itself in a module expression */
+
+    pub name: String,
+    pub exports: Vec<String>
+}
+
+
+pub struct Import {
+    /* An import expression */
+
+    pub path: String,
+
+    // the prefix of the module's qualified names, if not the module's own name
+    pub alias: Option<String>,
+
+    // the exports bound without qualification, and the names they are bound to
+    pub names: Vec<(String, String)>
+}
+
+
+pub struct Package {
+    /* A directory of modules described by a manifest, imported as <package>/<module> */
+
+    pub name: String,
+    pub version: Option<String>,
+    pub modules: Vec<String>,
+
+    // the names of the packages the package's modules import from
+    pub dependencies: Vec<String>
+}
+
+
+impl ModuleDeclaration {
+    pub fn parse(args: &Vec<Rc<Value>>) -> Result<Self, Error> {
+       /* Parses the arguments of a module expression
+        *
+        * A module expression has the form
+        *
+        *   (module <name> (export <name>..))
+        *
+        * and names the module in whose file it appears, and the definitions
+        * other files which import it can see.
+        */
+
+        let (name, export) = match args.as_slice() {
+            [name, export] if name.name() != "" => (name.name(), export),
+            _ => return new_error!("Liszp: expected syntax (module <name> (export <name>..))").into()
+        };
+
+        let exports = match export.to_list() {
+            Some(xs) if !xs.is_empty() && xs[0].name() == "export" && xs[1..].iter().all(|x| x.name() != "") => {
+                xs[1..].iter().map(|x| x.name()).collect()
+            },
+
+            _ => return new_error!("Liszp: expected (export <name>..) in declaration of module '{}'", name).into()
+        };
+
+        Ok(ModuleDeclaration { name, exports })
+    }
+}
+
+
+impl Import {
+    pub fn parse(args: &[Rc<Value>]) -> Result<Self, Error> {
+       /* Parses the arguments of an import expression
+        *
+        * An import expression has the form
+        *
+        *   (import "<path>" [:as <alias>] <import>..)
+        *
+        * where each import is either the name of an export, or a list
+        * (<name> :as <new name>) to bind it to a different name.
+        */
+
+        let (path, rest) = match args.split_first() {
+            Some((path, rest)) => {
+                match &**path {
+                    Value::String(s) => (s[1..s.len() - 1].to_string(), rest),
+                    _ => return new_error!("Liszp: expected a path string in import expression, received '{}'", path).into()
+                }
+            },
+
+            None => return new_error!("Liszp: expected syntax (import \"<path>\" [:as <alias>] <import>..)").into()
+        };
+
+        let (alias, imports) = match rest {
+            [keyword, alias, imports @ ..] if keyword.name() == ":as" && alias.name() != "" => (Some(alias.name()), imports),
+            _ => (None, rest)
+        };
+
+        let mut names = Vec::with_capacity(imports.len());
+
+        for import in imports.iter() {
+            match &**import {
+                Value::Name(name) => names.push((name.clone(), name.clone())),
+
+                _ => {
+                    match import.to_list().as_deref() {
+                        Some([name, keyword, new_name]) if keyword.name() == ":as" && name.name() != "" && new_name.name() != "" => {
+                            names.push((name.name(), new_name.name()));
+                        },
+
+                        _ => return new_error!("Liszp: expected <name> or (<name> :as <new name>) in import expression, received '{}'", import).into()
+                    }
+                }
+            }
+        }
+
+        Ok(Import { path, alias, names })
+    }
+}
+
+
+impl Package {
+    pub fn parse(manifest: &Vec<Rc<Value>>) -> Result<Self, Error> {
+       /* Parses a package manifest
+        *
+        * A manifest contains one expression of the form
+        *
+        *   (package <name>
+        *       (version "<version>")
+        *       (modules <module>..)
+        *       (dependencies <package>..))
+        *
+        * where the modules are the files <module>.lzp in the package's
+        * directory which other code may import, and each clause is optional.
+        */
+
+        let components = match manifest.as_slice() {
+            [package] => package.to_list().unwrap_or_default(),
+            _ => vec![]
+        };
+
+        let (name, clauses) = match components.as_slice() {
+            [keyword, name, clauses @ ..] if keyword.name() == "package" && name.name() != "" => (name.name(), clauses),
+            _ => return new_error!("Liszp: expected a package manifest (package <name> <clause>..)").into()
+        };
+
+        let mut package = Package { name, version: None, modules: vec![], dependencies: vec![] };
+
+        for clause in clauses.iter() {
+            let (keyword, values) = match clause.to_list().as_ref().map(|xs| xs.split_first()) {
+                Some(Some((keyword, values))) => (keyword.name(), values.to_vec()),
+                _ => return new_error!("Liszp: expected clause (<keyword> <value>..) in manifest of package '{}', received '{}'", package.name, clause).into()
+            };
+
+            let names = || -> Result<Vec<String>, Error> {
+                match values.iter().find(|v| v.name() == "") {
+                    Some(v) => new_error!("Liszp: expected names in '{}' clause of package '{}', received '{}'", keyword, package.name, v).into(),
+                    None => Ok(values.iter().map(|v| v.name()).collect())
+                }
+            };
+
+            match (keyword.as_str(), values.as_slice()) {
+                ("version", [version]) => package.version = Some(version.to_string().trim_matches('"').to_string()),
+                ("modules", _) => package.modules = names()?,
+                ("dependencies", _) => package.dependencies = names()?,
+                _ => return new_error!("Liszp: unknown clause '{}' in manifest of package '{}'", clause, package.name).into()
+            }
+        }
+
+        Ok(package)
+    }
+}
+
+
+/* Search path */
+
+
+pub fn stdlib_dir() -> PathBuf {
+   /* Returns the directory of the standard library
+    *
+    * This is LISZP_STDLIB if it is set. Otherwise it is looked for next to
+    * the liszp executable or in a directory above it, either as liszp-stdlib
+    * or installed as share/liszp/liszp-stdlib, and lastly where liszp was
+    * built.
+    */
+
+    if let Some(dir) = std::env::var_os("LISZP_STDLIB") {
+        return PathBuf::from(dir);
+    }
+
+    let executable = std::env::current_exe().ok().and_then(|exe| exe.canonicalize().ok());
+
+    let installed = executable.as_ref().and_then(|exe| {
+        exe.ancestors()
+           .skip(1)
+           .flat_map(|dir| vec![ dir.join("liszp-stdlib"), dir.join("share/liszp/liszp-stdlib") ])
+           .find(|dir| dir.join(PRELUDE_FILENAMES[0]).is_file())
+    });
+
+    installed.unwrap_or_else(|| PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/liszp-stdlib")))
+}
+
+
+pub fn search_path(project_root: &Path) -> Vec<PathBuf> {
+   /* Returns the directories imports are searched for in, in order
+    *
+    * These are the lib directory of the project, then the directories in
+    * the LISZP_PATH environment variable, then the standard library.
+    */
+
+    let mut dirs = vec![ project_root.join("lib") ];
+
+    if let Some(paths) = std::env::var_os("LISZP_PATH") {
+        dirs.extend(std::env::split_paths(&paths).filter(|dir| !dir.as_os_str().is_empty()));
+    }
+
+    dirs.push(stdlib_dir());
+
+    dirs
+}
+
+
+pub fn resolve(path: &str, importer_dir: &Path, search_path: &[PathBuf]) -> Option<PathBuf> {
+   /* Finds the file an import refers to
+    *
+    * The .lzp extension may be left out. Paths starting with ./ or ../ are
+    * only looked for relative to the importing file, and other paths are
+    * looked for there and then in each directory of the search path.
+    */
+
+    let filename = if path.ends_with(".lzp") { path.to_string() } else { format!("{}.lzp", path) };
+    let is_relative = path.starts_with("./") || path.starts_with("../") || Path::new(path).is_absolute();
+
+    let dirs: Vec<&Path> = if is_relative {
+        vec![ importer_dir ]
+    } else {
+        std::iter::once(importer_dir).chain(search_path.iter().map(|dir| dir.as_path())).collect()
+    };
+
+    dirs.into_iter()
+        .map(|dir| dir.join(&filename))
+        .find(|file| file.is_file())
+        .and_then(|file| file.canonicalize().ok())
+}
+
+
+pub fn find_package(name: &str, search_path: &[PathBuf]) -> Option<PathBuf> {
+    /* Returns the directory of a package on the search path */
+
+    search_path.iter()
+               .map(|dir| dir.join(name))
+               .find(|dir| dir.join(MANIFEST_FILENAME).is_file())
+}
@@ -1,7 +1,6 @@
 /* A module for arithmetic, logic, and comparative operators */
 
 use crate::error::Error;
-use crate::eval::Evaluator;
 use crate::new_error;
 use crate::value::Value;
 use itertools::Itertools;
@@ -12,7 +11,7 @@ use std::rc::Rc;
 /* Arithmetic */
 
 
-pub fn arithmetic_expression(op: &String, args: &Vec<Rc<Value>>, evaluator: &mut Evaluator) -> Result<Rc<Value>, Error> {
+pub fn arithmetic_expression(op: &String, args: &[Rc<Value>]) -> Result<Rc<Value>, Error> {
     /* Computes an arithmetic expression */
 
     if args.len() < 2 {
@@ -23,9 +22,7 @@ pub fn arithmetic_expression(op: &String, args: &Vec<Rc<Value>>, evaluator: &mut
     let mut result_is_float = false;
 
     for arg in args.iter() {
-        let arg = evaluator.eval(arg)?;
-
-        match &*arg {
+        match &**arg {
             Value::Float(_) => {
                 result_is_float = true;
                 numbers.push(arg.clone());
@@ -117,12 +114,12 @@ fn integer_arithmetic(op: &String, args: &Vec<Rc<Value>>) -> Rc<Value> {
 }
 
 
-pub fn modulo(args: &Vec<Rc<Value>>, evaluator: &mut Evaluator) -> Result<Rc<Value>, Error> {
+pub fn modulo(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
     /* Takes the modulus of a number */
 
     match args.as_slice() {
         [dividend, divisor] => {
-            match (&*evaluator.eval(dividend)?, &*evaluator.eval(divisor)?) {
+            match (&**dividend, &**divisor) {
                 (Value::Float(x), Value::Float(y)) => Ok(Value::Float(x.clone() % y.clone()).rc()),
 
                 (Value::Float(_), Value::Integer(_)) => new_error!("cannot take the integer modulo of a float").into(),
@@ -140,20 +137,20 @@ pub fn modulo(args: &Vec<Rc<Value>>, evaluator: &mut Evaluator) -> Result<Rc<Val
 
 /* Logic */
 
-pub fn logical_operation(op: &String, args: &Vec<Rc<Value>>, evaluator: &mut Evaluator) -> Result<Rc<Value>, Error> {
+pub fn logical_operation(op: &String, args: &[Rc<Value>]) -> Result<Rc<Value>, Error> {
     /* Evaluates a binary logical operation */
 
     if args.len() < 2 {
         return new_error!("cannot apply '{}' expression to only {} arg", op, args.len()).into();
     }
 
-    let mut boolean_result = match &*evaluator.eval(&args[0])? {
+    let mut boolean_result = match &*args[0] {
         Value::Bool(b) => *b,
         _ => return new_error!("{} expressions take boolean arguments", op).into()
     };
 
     for arg in args[1..].iter() {
-        let boolean = match &*evaluator.eval(arg)? {
+        let boolean = match &**arg {
             Value::Bool(b) => *b,
             _ => return new_error!("{} expressions take boolean arguments", op).into()
         };
@@ -170,12 +167,12 @@ pub fn logical_operation(op: &String, args: &Vec<Rc<Value>>, evaluator: &mut Eva
 }
 
 
-pub fn logical_negation(args: &Vec<Rc<Value>>, evaluator: &mut Evaluator) -> Result<Rc<Value>, Error> {
+pub fn logical_negation(args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
     /* Performs a logical not operation */
 
     match args.as_slice() {
         [x] => {
-            match &*evaluator.eval(x)? {
+            match &**x {
                 Value::Bool(b) => Ok(Value::Bool(!*b).rc()),
                 _ => new_error!("'not' expressions take a boolean argument").into()
             }
@@ -189,12 +186,12 @@ pub fn logical_negation(args: &Vec<Rc<Value>>, evaluator: &mut Evaluator) -> Res
 /* Comparison */
 
 
-pub fn comparison(op: &String, args: &Vec<Rc<Value>>, evaluator: &mut Evaluator) -> Result<Rc<Value>, Error> {
+pub fn comparison(op: &String, args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
     /* Compares two values */
 
     match args.as_slice() {
         [x, y] => {
-            match (&*evaluator.eval(x)?, &*evaluator.eval(y)?) {
+            match (&**x, &**y) {
                 (Value::Integer(x), Value::Integer(y)) => {
                     Ok(integer_comparison(op, x, y))
                 }
@@ -0,0 +1,530 @@
+use crate::error::Error;
+use crate::eval::{ Environment, StructOperation, StructType };
+use crate::new_error;
+use crate::value::Value;
+use std::rc::Rc;
+
+
+#[derive(Debug)]
+pub enum Pattern {
+    /* A pattern which a value can be matched against */
+
+    // _, which matches anything
+    Wildcard,
+
+    // a name, which matches anything and binds it
+    Bind(String),
+
+    // a literal or quoted value, which matches values equal to it
+    Literal(Rc<Value>),
+
+    // (cons <car> <cdr>), and the (list ..) patterns built out of it
+    Cons(Box<Pattern>, Box<Pattern>),
+
+    // (<constructor> <field>..), which matches structs of a given type
+    Struct {
+        kind: Rc<StructType>,
+        fields: Vec<Pattern>
+    }
+}
+
+
+#[derive(Debug)]
+pub struct Clause {
+    /* A clause of a match expression */
+
+    pub pattern: Pattern,
+    pub guard: Option<Rc<Value>>,
+    pub body: Vec<Rc<Value>>
+}
+
+
+impl Pattern {
+
+    /* Parsing */
+
+    pub fn parse(expr: &Rc<Value>, env: &Rc<Environment>) -> Result<Self, Error> {
+       /* Parses a pattern
+        *
+        * A pattern is one of
+        *
+        *   _                      matches anything
+        *   <name>                 matches anything, binding it to name
+        *   <literal>              matches an equal number, string, bool, nil or keyword
+        *   '<value>               matches a value equal to the quoted value
+        *   (cons <car> <cdr>)     matches a cons pair
+        *   (list <pattern>..)     matches a list with one element per pattern
+        *   (list <pattern>.. . <rest>)
+        *                          matches a list with at least as many elements,
+        *                          matching its remaining elements against rest
+        *   (<constructor> <pattern>..)
+        *                          matches a struct created by constructor, with
+        *                          one pattern per field
+        *
+        * Names are looked up in env to tell constructors apart, so the name
+        * of a variant without fields matches that variant instead of binding.
+        */
+
+        let pattern = Self::parse_pattern(expr, env)?;
+
+        pattern.check_for_duplicates()?;
+
+        Ok(pattern)
+    }
+
+
+    fn parse_pattern(expr: &Rc<Value>, env: &Rc<Environment>) -> Result<Self, Error> {
+        /* Parses a pattern without checking the names it binds */
+
+        let parse_element = |element: &Rc<Value>| Self::parse_pattern(element, env);
+
+        match &**expr {
+            Value::Name(name) if name == "_" => Ok(Pattern::Wildcard),
+
+            Value::Name(name) if name.len() > 1 && name.starts_with(':') => Ok(Pattern::Literal(expr.clone())),
+
+            Value::Name(name) => {
+                match env.get(name).as_deref() {
+                    Some(Value::Struct { kind, fields }) if kind.sum.is_some() && fields.is_empty() => {
+                        Ok(Pattern::Struct { kind: kind.clone(), fields: vec![] })
+                    },
+
+                    _ => Ok(Pattern::Bind(name.clone()))
+                }
+            },
+
+            Value::Cons { .. } => {
+                let components = match expr.to_list() {
+                    Some(xs) => xs,
+                    None => return new_error!("Liszp: invalid pattern '{}'", expr).into()
+                };
+
+                match components.split_first() {
+                    Some((head, args)) => {
+                        match (head.name().as_str(), args) {
+                            ("quote", [value]) => Ok(Pattern::Literal(value.clone())),
+
+                            ("cons", [car, cdr]) => {
+                                Ok(Pattern::Cons(
+                                    Box::new(parse_element(car)?),
+                                    Box::new(parse_element(cdr)?)
+                                ))
+                            },
+
+                            ("list", elements) => Self::parse_list(elements, &parse_element),
+
+                            (constructor, fields) => Self::parse_struct(expr, constructor, fields, env)
+                        }
+                    },
+
+                    None => new_error!("Liszp: invalid pattern '{}'", expr).into()
+                }
+            },
+
+            _ => Ok(Pattern::Literal(expr.clone()))
+        }
+    }
+
+
+    fn parse_struct(expr: &Rc<Value>, constructor: &str, fields: &[Rc<Value>], env: &Rc<Environment>) -> Result<Self, Error> {
+        /* Parses a pattern matching the structs created by a constructor */
+
+        let kind = match env.get(constructor).as_deref() {
+            Some(Value::StructFunction { kind, operation: StructOperation::Construct, .. }) => kind.clone(),
+            Some(Value::Struct { kind, .. }) if kind.sum.is_some() && kind.fields.is_empty() => kind.clone(),
+            _ => return new_error!("Liszp: invalid pattern '{}'", expr).into()
+        };
+
+        if fields.len() != kind.fields.len() {
+            return new_error!("Liszp: pattern '{}' expected {} fields but received {}", expr, kind.fields.len(), fields.len()).into();
+        }
+
+        let fields = fields.iter()
+                           .map(|field| Self::parse_pattern(field, env))
+                           .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Pattern::Struct { kind, fields })
+    }
+
+
+    pub fn parse_binder(expr: &Rc<Value>) -> Result<Self, Error> {
+       /* Parses a destructuring parameter
+        *
+        * A destructuring parameter is a name, _ or a list of destructuring
+        * parameters such as (a (b c) . rest), which matches lists of the same
+        * shape and binds their elements.
+        */
+
+        let pattern = Self::parse_binder_pattern(expr)?;
+
+        pattern.check_for_duplicates()?;
+
+        Ok(pattern)
+    }
+
+
+    fn parse_binder_pattern(expr: &Rc<Value>) -> Result<Self, Error> {
+        /* Parses a destructuring parameter without checking the names it binds */
+
+        match &**expr {
+            Value::Name(name) if name == "." => new_error!("Liszp: unexpected '.' in destructuring parameter").into(),
+
+            Value::Name(name) if name == "_" => Ok(Pattern::Wildcard),
+
+            Value::Name(name) => Ok(Pattern::Bind(name.clone())),
+
+            Value::Nil => Ok(Pattern::Literal(expr.clone())),
+
+            Value::Cons { .. } => {
+                match expr.to_list() {
+                    Some(elements) => Self::parse_list(&elements, &Self::parse_binder_pattern),
+                    None => new_error!("Liszp: invalid destructuring parameter '{}'", expr).into()
+                }
+            },
+
+            _ => new_error!("Liszp: expected a name or a list of names in destructuring parameter, received '{}'", expr).into()
+        }
+    }
+
+
+    fn parse_list(elements: &[Rc<Value>], parse_element: &dyn Fn(&Rc<Value>) -> Result<Self, Error>) -> Result<Self, Error> {
+        /* Parses the elements of a list pattern into nested cons patterns */
+
+        let (elements, mut pattern) = match elements {
+            [init @ .., dot, rest] if dot.name() == "." => (init, parse_element(rest)?),
+            _ => (elements, Pattern::Literal(Value::Nil.rc()))
+        };
+
+        for element in elements.iter().rev() {
+            if element.name() == "." {
+                return new_error!("Liszp: '.' must come just before the last pattern of a list pattern").into();
+            }
+
+            pattern = Pattern::Cons(Box::new(parse_element(element)?), Box::new(pattern));
+        }
+
+        Ok(pattern)
+    }
+
+
+    fn check_for_duplicates(&self) -> Result<(), Error> {
+        /* Ensures that no name is bound twice by the pattern */
+
+        let mut names = self.names();
+
+        names.sort();
+
+        for pair in names.windows(2) {
+            if pair[0] == pair[1] {
+                return new_error!("Liszp: name '{}' is bound more than once in pattern", pair[0]).into();
+            }
+        }
+
+        Ok(())
+    }
+
+
+    pub fn names(&self) -> Vec<&String> {
+        /* Returns every name bound by the pattern */
+
+        let mut names = vec![];
+
+        self.collect_names(&mut names);
+
+        names
+    }
+
+
+    fn collect_names<'a>(&'a self, names: &mut Vec<&'a String>) {
+        /* Adds the names bound by the pattern to names */
+
+        match self {
+            Pattern::Bind(name) => names.push(name),
+
+            Pattern::Cons(car, cdr) => {
+                car.collect_names(names);
+                cdr.collect_names(names);
+            },
+
+            Pattern::Struct { fields, .. } => {
+                for field in fields.iter() {
+                    field.collect_names(names);
+                }
+            },
+
+            _ => {}
+        }
+    }
+
+
+    /* Matching */
+
+
+    pub fn bind(&self, value: &Rc<Value>, env: &Rc<Environment>) -> bool {
+        /* Matches a value against the pattern, defining the names it binds in env if it matches */
+
+        let mut bindings = vec![];
+
+        if !self.matches(value, &mut bindings) {
+            return false;
+        }
+
+        for (name, value) in bindings.into_iter() {
+            env.define(name, value);
+        }
+
+        true
+    }
+
+
+    fn matches<'a>(&'a self, value: &Rc<Value>, bindings: &mut Vec<(&'a String, Rc<Value>)>) -> bool {
+        /* Matches a value against the pattern, collecting the values of the names it binds */
+
+        match self {
+            Pattern::Wildcard => true,
+
+            Pattern::Bind(name) => {
+                bindings.push((name, value.clone()));
+                true
+            },
+
+            Pattern::Literal(literal) => literal == value,
+
+            Pattern::Cons(car, cdr) => {
+                match value.uncons() {
+                    Some((x, xs)) => car.matches(&x, bindings) && cdr.matches(&xs, bindings),
+                    None => false
+                }
+            },
+
+            Pattern::Struct { kind, fields: patterns } => {
+                match &**value {
+                    Value::Struct { kind: value_kind, fields } if Rc::ptr_eq(kind, value_kind) => {
+                        patterns.iter()
+                                .zip(fields.iter())
+                                .all(|(pattern, field)| pattern.matches(&field.borrow(), bindings))
+                    },
+
+                    _ => false
+                }
+            }
+        }
+    }
+
+
+    fn is_irrefutable(&self) -> bool {
+        /* Returns whether the pattern matches every value */
+
+        matches!(self, Pattern::Wildcard | Pattern::Bind(_))
+    }
+
+
+    pub fn destructure(&self, value: &Rc<Value>, env: &Rc<Environment>) -> Result<(), Error> {
+        /* Binds a value to a destructuring parameter, explaining why if its shape doesn't match */
+
+        if self.bind(value, env) {
+            return Ok(());
+        }
+
+        let reason = self.mismatch(value).unwrap_or_else(|| "values are not equal".into());
+
+        new_error!("cannot destructure '{}' with parameter '{}': {}", value, self, reason).into()
+    }
+
+
+    fn mismatch(&self, value: &Rc<Value>) -> Option<String> {
+        /* Describes the first part of a value which doesn't match the pattern */
+
+        match (self, &**value) {
+            (Pattern::Cons(_, _), Value::Nil) => Some("list has too few elements".into()),
+
+            (Pattern::Cons(car, cdr), Value::Cons { .. }) => {
+                let (x, xs) = value.uncons().unwrap();
+
+                car.mismatch(&x).or_else(|| cdr.mismatch(&xs))
+            },
+
+            (Pattern::Cons(_, _), _) => Some(format!("expected a list but received '{}'", value)),
+
+            (Pattern::Literal(literal), Value::Cons { .. }) if **literal == Value::Nil => {
+                Some("list has too many elements".into())
+            },
+
+            (Pattern::Literal(literal), _) if literal != value => {
+                Some(format!("expected '{}' but received '{}'", literal, value))
+            },
+
+            _ => None
+        }
+    }
+
+
+    pub fn covers(&self, other: &Pattern) -> bool {
+        /* Returns whether every value matched by other is also matched by the pattern */
+
+        match (self, other) {
+            (a, _) if a.is_irrefutable() => true,
+
+            (Pattern::Literal(a), Pattern::Literal(b)) => a == b,
+
+            (Pattern::Cons(a, xs), Pattern::Cons(b, ys)) => a.covers(b) && xs.covers(ys),
+
+            (Pattern::Struct { kind: a, fields: xs }, Pattern::Struct { kind: b, fields: ys }) => {
+                Rc::ptr_eq(a, b) && xs.iter().zip(ys.iter()).all(|(x, y)| x.covers(y))
+            },
+
+            _ => false
+        }
+    }
+}
+
+
+impl std::fmt::Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        /* Displays the pattern in destructuring parameter syntax */
+
+        match self {
+            Pattern::Wildcard => write!(f, "_"),
+
+            Pattern::Bind(name) => write!(f, "{}", name),
+
+            Pattern::Literal(literal) if **literal == Value::Nil => write!(f, "()"),
+
+            Pattern::Literal(literal) => write!(f, "'{}", literal),
+
+            Pattern::Cons(car, cdr) => {
+                let mut components = vec![ car.to_string() ];
+                let mut cursor = &**cdr;
+
+                while let Pattern::Cons(x, xs) = cursor {
+                    components.push(x.to_string());
+                    cursor = &**xs;
+                }
+
+                match cursor {
+                    Pattern::Literal(literal) if **literal == Value::Nil => {},
+                    tail => components.push(format!(". {}", tail))
+                }
+
+                write!(f, "({})", components.join(" "))
+            },
+
+            Pattern::Struct { kind, fields } if fields.is_empty() && kind.sum.is_some() => write!(f, "{}", kind.name),
+
+            Pattern::Struct { kind, fields } => {
+                let mut components = vec![ kind.constructor_name() ];
+
+                components.extend(fields.iter().map(|p| p.to_string()));
+
+                write!(f, "({})", components.join(" "))
+            }
+        }
+    }
+}
+
+
+impl Clause {
+    pub fn parse(expr: &Rc<Value>, env: &Rc<Environment>) -> Result<Self, Error> {
+       /* Parses a clause of a match expression
+        *
+        * A clause has the form
+        *
+        *   (<pattern> <body>..)
+        *
+        * or (<pattern> :when <guard> <body>..), where the clause only applies
+        * if the guard evaluates to true with the pattern's names bound.
+        */
+
+        match expr.to_list().as_deref() {
+            Some([pattern, when, guard, body @ ..]) if when.name() == ":when" => {
+                Ok(Clause {
+                    pattern: Pattern::parse(pattern, env)?,
+                    guard: Some(guard.clone()),
+                    body: body.to_vec()
+                })
+            },
+
+            Some([pattern, body @ ..]) => {
+                Ok(Clause {
+                    pattern: Pattern::parse(pattern, env)?,
+                    guard: None,
+                    body: body.to_vec()
+                })
+            },
+
+            _ => new_error!("Liszp: expected match clause (<pattern> <body>..), received '{}'", expr).into()
+        }
+    }
+
+
+    pub fn parse_clauses(exprs: &[Rc<Value>], env: &Rc<Environment>) -> Result<Vec<Self>, Error> {
+       /* Parses the clauses of a match expression
+        *
+        * Clauses which can never match are rejected, as are clauses matching
+        * the variants of a sum type which leave some of its variants out.
+        */
+
+        let mut clauses: Vec<Clause> = Vec::with_capacity(exprs.len());
+
+        for (i, expr) in exprs.iter().enumerate() {
+            let clause = Self::parse(expr, env)?;
+
+            let covered = clauses.iter().any(|earlier| {
+                earlier.guard.is_none() && earlier.pattern.covers(&clause.pattern)
+            });
+
+            if covered {
+                return new_error!("Liszp: clause {} of match expression is unreachable: '{}'", i + 1, expr).into();
+            }
+
+            clauses.push(clause);
+        }
+
+        Self::check_exhaustive(&clauses)?;
+
+        Ok(clauses)
+    }
+
+
+    fn check_exhaustive(clauses: &[Self]) -> Result<(), Error> {
+        /* Ensures that clauses matching the variants of a sum type match all of them */
+
+        let sum = clauses.iter().find_map(|clause| match &clause.pattern {
+            Pattern::Struct { kind, .. } => kind.sum.clone(),
+            _ => None
+        });
+
+        let sum = match sum {
+            Some(sum) => sum,
+            None => return Ok(())
+        };
+
+        let unguarded: Vec<&Pattern> = clauses.iter()
+                                              .filter(|clause| clause.guard.is_none())
+                                              .map(|clause| &clause.pattern)
+                                              .collect();
+
+        if unguarded.iter().any(|pattern| pattern.is_irrefutable()) {
+            return Ok(());
+        }
+
+        let missing: Vec<&String> = sum.variants.iter()
+                                                .filter(|variant| !unguarded.iter().any(|pattern| match pattern {
+                                                    Pattern::Struct { kind, fields } => {
+                                                        kind.name == **variant
+                                                            && kind.sum.as_ref().is_some_and(|s| Rc::ptr_eq(s, &sum))
+                                                            && fields.iter().all(|field| field.is_irrefutable())
+                                                    },
+                                                    _ => false
+                                                }))
+                                                .collect();
+
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            let missing: Vec<String> = missing.iter().map(|v| format!("'{}'", v)).collect();
+
+            new_error!("Liszp: match expression on type '{}' is not exhaustive: missing {}", sum.name, missing.join(", ")).into()
+        }
+    }
+}
@@ -0,0 +1,338 @@
+use crate::error::Error;
+use crate::eval::{ Environment, Pattern };
+use crate::new_error;
+use crate::value::Value;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+
+#[derive(Debug)]
+pub struct Parameter {
+    name: String,
+    default: Option<Rc<Value>>
+}
+
+
+#[derive(Debug)]
+pub struct Signature {
+    required: Vec<Pattern>,
+    optional: Vec<Parameter>,
+    keyword: Vec<Parameter>,
+    rest: Option<String>
+}
+
+
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+enum Section {
+    Required,
+    Optional,
+    Keyword
+}
+
+
+impl Signature {
+
+    /* Parsing */
+
+    pub fn parse(arg_component: &Rc<Value>) -> Result<Self, Error> {
+       /* Parses the parameter list of a lambda expression
+        *
+        * A parameter list has the form
+        *
+        *   (<required>.. ? <optional>.. : <keyword>.. @ <rest>)
+        *
+        * where every section is optional, required parameters are names or
+        * nested lists of names which destructure their argument, optional and
+        * keyword parameters are either a name or a list [<name> <default>],
+        * and a single name in place of the list takes one required argument.
+        */
+
+        let mut signature = Signature {
+            required: vec![],
+            optional: vec![],
+            keyword: vec![],
+            rest: None
+        };
+
+        let components = match &**arg_component {
+            Value::Cons {..} => arg_component.to_list().unwrap(),
+
+            Value::Name(name) => {
+                signature.required.push(Pattern::Bind(name.clone()));
+
+                return Ok(signature);
+            },
+
+            Value::Nil => return Ok(signature),
+
+            _ => return new_error!("Liszp: Function expected a list of arguments or a single argument in lambda expression").into()
+        };
+
+        let mut section = Section::Required;
+        let mut i = 0;
+
+        while i < components.len() {
+            match components[i].name().as_str() {
+                "?" => section = Self::enter_section(section, Section::Optional, "?")?,
+
+                ":" => section = Self::enter_section(section, Section::Keyword, ":")?,
+
+                "@" => {
+                    if i + 2 != components.len() {
+                        return new_error!("Liszp: a rest parameter must be the last parameter of a function").into();
+                    }
+
+                    match &*components[i + 1] {
+                        Value::Name(name) => signature.rest = Some(name.clone()),
+                        _ => return new_error!("Liszp: Expected name for rest parameter").into()
+                    }
+
+                    break;
+                },
+
+                _ if section == Section::Required => {
+                    signature.required.push(Pattern::parse_binder(&components[i])?);
+                },
+
+                _ => {
+                    let parameter = Self::parse_parameter(&components[i])?;
+
+                    match section {
+                        Section::Optional => signature.optional.push(parameter),
+                        _ => signature.keyword.push(parameter)
+                    }
+                }
+            }
+
+            i += 1;
+        }
+
+        signature.check_for_duplicates()?;
+
+        Ok(signature)
+    }
+
+
+    fn enter_section(current: Section, next: Section, marker: &str) -> Result<Section, Error> {
+        /* Moves the parser on to the next section of a parameter list */
+
+        if current >= next {
+            return new_error!("Liszp: unexpected '{}' in function parameters", marker).into();
+        }
+
+        Ok(next)
+    }
+
+
+    fn parse_parameter(component: &Rc<Value>) -> Result<Parameter, Error> {
+        /* Parses a single optional or keyword parameter, which may have a default */
+
+        if let Value::Name(name) = &**component {
+            return Ok(Parameter { name: name.clone(), default: None });
+        }
+
+        if let Some(xs) = component.to_list() {
+            if let [name, default] = xs.as_slice() {
+                if let Value::Name(name) = &**name {
+                    return Ok(Parameter { name: name.clone(), default: Some(default.clone()) });
+                }
+            }
+        }
+
+        new_error!("Liszp: Expected name or [<name> <default>] in function argument").into()
+    }
+
+
+    fn check_for_duplicates(&self) -> Result<(), Error> {
+        /* Ensures that no name is bound twice by the signature */
+
+        let mut names = self.names();
+
+        names.sort();
+
+        for pair in names.windows(2) {
+            if pair[0] == pair[1] {
+                return new_error!("Liszp: parameter '{}' appears more than once in function signature", pair[0]).into();
+            }
+        }
+
+        Ok(())
+    }
+
+
+    fn names(&self) -> Vec<&String> {
+        /* Returns every name bound by the signature */
+
+        let mut names: Vec<&String> = self.required.iter().flat_map(|p| p.names()).collect();
+
+        names.extend(self.optional.iter().map(|p| &p.name));
+        names.extend(self.keyword.iter().map(|p| &p.name));
+        names.extend(self.rest.iter());
+
+        names
+    }
+
+
+    /* Binding */
+
+
+    pub fn bind(&self, arg_values: Vec<Rc<Value>>, frame: &Rc<Environment>) -> Result<Vec<(String, Rc<Value>)>, Error> {
+       /* Binds the arguments of a call in frame
+        *
+        * returns
+        * -------
+        * the parameters which were not supplied and whose defaults must be
+        * evaluated, as (name, default expression) pairs in order
+        */
+
+        let mut positional = Vec::with_capacity(arg_values.len());
+        let mut keywords = HashMap::new();
+        let mut arg_values = arg_values.into_iter();
+
+        while let Some(value) = arg_values.next() {
+            match self.keyword_parameter(&value)? {
+                Some(name) => {
+                    let keyword_value = match arg_values.next() {
+                        Some(v) => v,
+                        None => return new_error!("keyword argument '{}' is missing a value", value).into()
+                    };
+
+                    if keywords.insert(name, keyword_value).is_some() {
+                        return new_error!("keyword argument '{}' was supplied more than once", value).into();
+                    }
+                },
+
+                None => positional.push(value)
+            }
+        }
+
+        self.check_arity(positional.len())?;
+
+        let mut positional = positional.into_iter();
+        let mut pending_defaults = vec![];
+
+        for pattern in self.required.iter() {
+            pattern.destructure(&positional.next().unwrap(), frame)?;
+        }
+
+        for parameter in self.optional.iter() {
+            parameter.bind(positional.next(), frame, &mut pending_defaults);
+        }
+
+        for parameter in self.keyword.iter() {
+            parameter.bind(keywords.remove(&parameter.name), frame, &mut pending_defaults);
+        }
+
+        if let Some(rest) = &self.rest {
+            frame.define(rest, Value::cons_list(&positional.collect()));
+        }
+
+        Ok(pending_defaults)
+    }
+
+
+    fn keyword_parameter(&self, value: &Rc<Value>) -> Result<Option<String>, Error> {
+        /* Returns the keyword parameter an argument names, if it is a keyword */
+
+        if self.keyword.is_empty() {
+            return Ok(None);
+        }
+
+        match &**value {
+            Value::Name(name) if name.len() > 1 && name.starts_with(':') => {
+                if self.keyword.iter().any(|p| p.name == name[1..]) {
+                    Ok(Some(name[1..].to_string()))
+                } else {
+                    new_error!("function has no keyword parameter '{}' (parameters: {})", name, self).into()
+                }
+            },
+
+            _ => Ok(None)
+        }
+    }
+
+
+    pub fn accepts(&self, positional_count: usize) -> bool {
+        /* Returns whether a call may supply a number of positional arguments */
+
+        let min = self.required.len();
+        let max = min + self.optional.len();
+
+        positional_count >= min && (self.rest.is_some() || positional_count <= max)
+    }
+
+
+    fn check_arity(&self, positional_count: usize) -> Result<(), Error> {
+        /* Ensures that a call supplied an acceptable number of positional arguments */
+
+        if self.accepts(positional_count) {
+            return Ok(());
+        }
+
+        let min = self.required.len();
+        let max = min + self.optional.len();
+
+        let expected = if self.rest.is_some() {
+            format!("at least {}", min)
+        } else if min == max {
+            format!("{}", min)
+        } else {
+            format!("between {} and {}", min, max)
+        };
+
+        new_error!("function expected {} arguments but received {} (parameters: {})", expected, positional_count, self).into()
+    }
+}
+
+
+impl Parameter {
+    fn bind(&self, value: Option<Rc<Value>>, frame: &Rc<Environment>, pending_defaults: &mut Vec<(String, Rc<Value>)>) {
+        /* Binds a supplied value, or nil or a pending default if none was supplied */
+
+        match (value, &self.default) {
+            (Some(v), _) => {
+                frame.define(&self.name, v);
+            },
+
+            (None, Some(default)) => pending_defaults.push((self.name.clone(), default.clone())),
+
+            (None, None) => {
+                frame.define(&self.name, Value::Nil.rc());
+            }
+        }
+    }
+}
+
+
+impl std::fmt::Display for Parameter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.default {
+            Some(default) => write!(f, "[{} {}]", self.name, default),
+            None => write!(f, "{}", self.name)
+        }
+    }
+}
+
+
+impl std::fmt::Display for Signature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut components: Vec<String> = self.required.iter().map(|p| p.to_string()).collect();
+
+        if !self.optional.is_empty() {
+            components.push("?".into());
+            components.extend(self.optional.iter().map(|p| p.to_string()));
+        }
+
+        if !self.keyword.is_empty() {
+            components.push(":".into());
+            components.extend(self.keyword.iter().map(|p| p.to_string()));
+        }
+
+        if let Some(rest) = &self.rest {
+            components.push("@".into());
+            components.push(rest.clone());
+        }
+
+        write!(f, "({})", components.join(" "))
+    }
+}
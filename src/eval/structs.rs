@@ -0,0 +1,325 @@
+use crate::error::Error;
+use crate::new_error;
+use crate::value::Value;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+
+#[derive(Debug)]
+pub struct StructType {
+    /* A record type created by a defstruct expression, or a variant of a sum type
+     *
+     * Struct types are nominal: two types with the same name and fields are
+     * still different types, so values are compared by the identity of
+     * their type.
+     */
+
+    pub name: String,
+    pub fields: Vec<String>,
+    pub mutable: bool,
+
+    // the sum type the struct type is a variant of, if it was defined by deftype
+    pub sum: Option<Rc<SumType>>
+}
+
+
+#[derive(Debug)]
+pub struct SumType {
+    /* A sum type created by a deftype expression, whose values are one of its variants */
+
+    pub name: String,
+    pub variants: Vec<String>
+}
+
+
+#[derive(Clone, Copy, Debug)]
+pub enum StructOperation {
+    /* The functions defined by a defstruct or deftype expression */
+
+    Construct,
+
+    Predicate,
+
+    // returns whether a value is any variant of the struct type's sum type
+    SumPredicate,
+
+    Get(usize),
+
+    Set(usize)
+}
+
+
+impl StructType {
+
+    /* Definition */
+
+    pub fn parse(args: &[Rc<Value>]) -> Result<Rc<Self>, Error> {
+       /* Parses the arguments of a defstruct expression
+        *
+        * A defstruct expression has the form
+        *
+        *   (defstruct <name> <field>..)
+        *
+        * or (defstruct (<name> :mutable) <field>..) to define setters too.
+        */
+
+        let (header, fields) = match args.split_first() {
+            Some(split) => split,
+            None => return new_error!("Liszp: expected syntax (defstruct <name> <field>..)").into()
+        };
+
+        let (name, mutable) = match &**header {
+            Value::Name(name) => (name.clone(), false),
+
+            Value::Cons { .. } => {
+                match header.to_list().as_deref() {
+                    Some([name, option]) if name.name() != "" && option.name() == ":mutable" => (name.name(), true),
+                    _ => return new_error!("Liszp: expected (<name> :mutable) in defstruct expression, received '{}'", header).into()
+                }
+            },
+
+            _ => return new_error!("Liszp: expected name in defstruct expression, received '{}'", header).into()
+        };
+
+        let fields = Self::parse_fields(&name, fields)?;
+
+        Ok(Rc::new(StructType { name, fields, mutable, sum: None }))
+    }
+
+
+    pub fn parse_sum(args: &[Rc<Value>]) -> Result<Vec<Rc<Self>>, Error> {
+       /* Parses the arguments of a deftype expression into the types of its variants
+        *
+        * A deftype expression has the form
+        *
+        *   (deftype <name> <variant>..)
+        *
+        * where each variant is either a name or a list (<name> <field>..).
+        */
+
+        let (name, variants) = match args.split_first() {
+            Some((name, variants)) if name.name() != "" && !variants.is_empty() => (name.name(), variants),
+            _ => return new_error!("Liszp: expected syntax (deftype <name> <variant>..)").into()
+        };
+
+        let mut parsed_variants = vec![];
+
+        for variant in variants.iter() {
+            let components = match &**variant {
+                Value::Name(_) => vec![ variant.clone() ],
+                Value::Cons { .. } => variant.to_list().unwrap(),
+                _ => return new_error!("Liszp: expected variant <name> or (<name> <field>..) in deftype expression").into()
+            };
+
+            match components.split_first() {
+                Some((variant_name, fields)) if variant_name.name() != "" => {
+                    if parsed_variants.iter().any(|(n, _)| *n == variant_name.name()) {
+                        return new_error!("Liszp: variant '{}' appears more than once in type '{}'", variant_name, name).into();
+                    }
+
+                    parsed_variants.push((variant_name.name(), Self::parse_fields(&variant_name.name(), fields)?));
+                },
+
+                _ => return new_error!("Liszp: expected variant <name> or (<name> <field>..) in deftype expression").into()
+            }
+        }
+
+        let sum = Rc::new(SumType {
+            name,
+            variants: parsed_variants.iter().map(|(n, _)| n.clone()).collect()
+        });
+
+        let variants = parsed_variants.into_iter()
+                                      .map(|(name, fields)| {
+                                          Rc::new(StructType {
+                                              name,
+                                              fields,
+                                              mutable: false,
+                                              sum: Some(sum.clone())
+                                          })
+                                      })
+                                      .collect();
+
+        Ok(variants)
+    }
+
+
+    fn parse_fields(name: &String, fields: &[Rc<Value>]) -> Result<Vec<String>, Error> {
+        /* Parses the field names of a struct type */
+
+        let mut field_names: Vec<String> = vec![];
+
+        for field in fields.iter() {
+            match &**field {
+                Value::Name(field) if !field_names.contains(field) => field_names.push(field.clone()),
+                Value::Name(field) => return new_error!("Liszp: field '{}' appears more than once in struct '{}'", field, name).into(),
+                _ => return new_error!("Liszp: expected field name in struct '{}', received '{}'", name, field).into()
+            }
+        }
+
+        Ok(field_names)
+    }
+
+
+    pub fn functions(self: &Rc<Self>) -> Vec<(String, Rc<Value>)> {
+       /* Returns the names and values of the functions a struct type defines
+        *
+        * The constructor of a variant of a sum type is named after the variant
+        * rather than make-<name>, and a variant without fields is defined as
+        * its only value instead of a constructor.
+        */
+
+        let mut definitions = vec![];
+
+        let mut functions = match &self.sum {
+            Some(_) if self.fields.is_empty() => {
+                definitions.push((self.name.clone(), Value::Struct { kind: self.clone(), fields: vec![] }.rc()));
+                vec![]
+            },
+
+            Some(_) => vec![ (self.name.clone(), StructOperation::Construct) ],
+
+            None => vec![ (format!("make-{}", self.name), StructOperation::Construct) ]
+        };
+
+        functions.push((format!("{}?", self.name), StructOperation::Predicate));
+
+        for (i, field) in self.fields.iter().enumerate() {
+            functions.push((format!("{}-{}", self.name, field), StructOperation::Get(i)));
+
+            if self.mutable {
+                functions.push((format!("set-{}-{}!", self.name, field), StructOperation::Set(i)));
+            }
+        }
+
+        definitions.extend(functions.into_iter().map(|(name, operation)| {
+            let function = Value::StructFunction {
+                kind: self.clone(),
+                operation,
+                name: name.clone()
+            };
+
+            (name, function.rc())
+        }));
+
+        definitions
+    }
+
+
+    pub fn constructor_name(&self) -> String {
+        /* Returns the name of the function which creates values of the struct type */
+
+        match &self.sum {
+            Some(_) => self.name.clone(),
+            None => format!("make-{}", self.name)
+        }
+    }
+
+
+    pub fn type_name(&self) -> &String {
+        /* Returns the name of the type of the struct type's values */
+
+        match &self.sum {
+            Some(sum) => &sum.name,
+            None => &self.name
+        }
+    }
+
+
+    /* Application */
+
+
+    pub fn apply(self: &Rc<Self>, operation: StructOperation, name: &String, args: &Vec<Rc<Value>>) -> Result<Rc<Value>, Error> {
+        /* Calls one of the functions defined for the struct type */
+
+        match (operation, args.as_slice()) {
+            (StructOperation::Construct, _) => self.construct(name, args),
+
+            (StructOperation::Predicate, [value]) => {
+                let res = match &**value {
+                    Value::Struct { kind, .. } => Rc::ptr_eq(kind, self),
+                    _ => false
+                };
+
+                Ok(Value::Bool(res).rc())
+            },
+
+            (StructOperation::SumPredicate, [value]) => {
+                let res = match (&**value, &self.sum) {
+                    (Value::Struct { kind, .. }, Some(sum)) => kind.sum.as_ref().is_some_and(|s| Rc::ptr_eq(s, sum)),
+                    _ => false
+                };
+
+                Ok(Value::Bool(res).rc())
+            },
+
+            (StructOperation::Get(i), [value]) => Ok(self.fields_of(name, value)?[i].borrow().clone()),
+
+            (StructOperation::Set(i), [value, new_value]) => {
+                self.fields_of(name, value)?[i].replace(new_value.clone());
+
+                Ok(Value::Nil.rc())
+            },
+
+            (StructOperation::Set(_), _) => new_error!("Liszp: function '{}' takes exactly two arguments", name).into(),
+
+            _ => new_error!("Liszp: function '{}' takes exactly one argument", name).into()
+        }
+    }
+
+
+    fn construct(self: &Rc<Self>, name: &String, args: &[Rc<Value>]) -> Result<Rc<Value>, Error> {
+       /* Creates a struct
+        *
+        * The fields are either given positionally, or as keyword arguments
+        * :<field> <value> in any order with any fields left out set to nil.
+        */
+
+        let is_keyword = |v: &Rc<Value>| matches!(&**v, Value::Name(n) if n.len() > 1 && n.starts_with(':'));
+
+        let values: Vec<Rc<Value>> = if !args.is_empty() && is_keyword(&args[0]) {
+            let mut values = vec![ None; self.fields.len() ];
+
+            for pair in args.chunks(2) {
+                let i = match (pair, pair[0].name()) {
+                    ([_, _], keyword) if is_keyword(&pair[0]) => {
+                        match self.fields.iter().position(|f| *f == keyword[1..]) {
+                            Some(i) => i,
+                            None => return new_error!("struct '{}' has no field '{}'", self.name, &keyword[1..]).into()
+                        }
+                    },
+
+                    _ => return new_error!("function '{}' expected keyword arguments :<field> <value>", name).into()
+                };
+
+                if values[i].replace(pair[1].clone()).is_some() {
+                    return new_error!("field '{}' was supplied more than once to '{}'", self.fields[i], name).into();
+                }
+            }
+
+            values.into_iter()
+                  .map(|v| v.unwrap_or_else(|| Value::Nil.rc()))
+                  .collect()
+        } else if args.len() == self.fields.len() {
+            args.to_vec()
+        } else {
+            return new_error!("function '{}' expected {} arguments but received {}", name, self.fields.len(), args.len()).into();
+        };
+
+        let fields = values.into_iter()
+                           .map(RefCell::new)
+                           .collect();
+
+        Ok(Value::Struct { kind: self.clone(), fields }.rc())
+    }
+
+
+    fn fields_of<'a>(self: &Rc<Self>, name: &String, value: &'a Rc<Value>) -> Result<&'a Vec<RefCell<Rc<Value>>>, Error> {
+        /* Returns the fields of a value if it is an instance of the struct type */
+
+        match &**value {
+            Value::Struct { kind, fields } if Rc::ptr_eq(kind, self) => Ok(fields),
+            _ => new_error!("function '{}' expected a struct of type '{}', received '{}'", name, self.name, value).into()
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use crate::eval::Signature;
+use crate::eval::pattern::Clause;
+use crate::value::Value;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+
+#[derive(Debug)]
+pub enum Syntax {
+    /* An expression parsed once when it is preprocessed, rather than each time it is evaluated */
+
+    // (lambda <params> <body>..)
+    Lambda {
+        signature: Rc<Signature>,
+        body: Rc<Value>,
+        source: Rc<Value>
+    },
+
+    // (match <expr> <clause>..), whose parsed clauses are kept with the number of types defined when they were parsed
+    Match {
+        value: Rc<Value>,
+        clauses: Vec<Rc<Value>>,
+        parsed: RefCell<Option<(usize, Rc<Vec<Clause>>)>>,
+        source: Rc<Value>
+    }
+}
+
+
+impl Syntax {
+    pub fn source(&self) -> &Rc<Value> {
+        /* Returns the expression the syntax was parsed from */
+
+        match self {
+            Syntax::Lambda { source, .. } | Syntax::Match { source, .. } => source
+        }
+    }
+}
@@ -1,13 +1,14 @@
-/* This module is currently parked 
+/* A module for defining and expanding macros
  *
- * While the evaluator is reconfigured to include preprocessing,
- * This module will exist but not be used.
+ * Macros are expanded in a separate phase before an expression is
+ * evaluated, see Evaluator::preprocess.
  */
 
 use crate::error::Error;
-use crate::eval::Evaluator;
+use crate::eval::{ Evaluator, Pattern };
 use crate::new_error;
 use crate::refcount_list;
+use crate::syntax_rules::SyntaxRules;
 use crate::value::Value;
 use std::rc::Rc;
 
@@ -18,8 +19,20 @@ use std::rc::Rc;
 #[derive(Clone)]
 pub struct Macro {
     name: Rc<Value>,
-    args: MacroArgs,
-    macro_as_function: Rc<Value>
+    transformer: Transformer
+}
+
+
+#[derive(Clone)]
+enum Transformer {
+    // defined by defmacro, as a function from the unevaluated arguments to the expansion
+    Function {
+        args: MacroArgs,
+        macro_as_function: Rc<Value>
+    },
+
+    // defined by define-syntax
+    Rules(Rc<SyntaxRules>)
 }
 
 
@@ -76,8 +89,8 @@ impl Macro {
     }
 
 
-    fn parse_macro_definition(expr: &Rc<Value>) -> Result<Option<Self>, Error> {
-       /* Parses a macro definition if one is defined in expr
+    fn parse_macro_definition(expr: &Rc<Value>, local_names: &[String], evaluator: &mut Evaluator) -> Result<Option<Self>, Error> {
+       /* Parses a macro definition if one is defined in expr, where local_names are bound
         *
         * returns
         * -------
@@ -90,6 +103,10 @@ impl Macro {
             Some(xs) => xs,
             None => return Ok(None)
         };
+
+        if !components.is_empty() && components[0].name() == "define-syntax" {
+            return Self::parse_syntax_definition(&components, local_names, evaluator);
+        }
     
         if components.is_empty() || components[0].name() != "defmacro" {
             return Ok(None);
@@ -115,18 +132,37 @@ impl Macro {
 
         let macro_args = Self::parse_macro_args(macro_name.name(), &signature_components[1..])?;
 
-        let macro_as_function = Self::macro_as_function(&macro_args, &components[2]);
+        // the body is preprocessed now, as it isn't when the macro is called
+        let body = evaluator.preprocess(&components[2])?;
+        let macro_as_function = Self::macro_as_function(&macro_args, &body);
 
         Ok(Some(
             Macro {
                 name: macro_name,
-                args: macro_args,
-                macro_as_function: Evaluator::parse_lambdas(&macro_as_function)?
+                transformer: Transformer::Function {
+                    args: macro_args,
+                    macro_as_function: Evaluator::parse_lambdas(&macro_as_function, evaluator.get_globals())?
+                }
             }
         ))
     }
 
 
+    fn parse_syntax_definition(components: &Vec<Rc<Value>>, local_names: &[String], evaluator: &mut Evaluator) -> Result<Option<Self>, Error> {
+        /* Parses a macro definition of the form (define-syntax <name> (syntax-rules ..)) */
+
+        match components.as_slice() {
+            [_define_syntax, name, spec] if name.name() != "" => {
+                let rules = SyntaxRules::parse(&name.name(), spec, evaluator.get_globals(), local_names)?;
+
+                Ok(Some(Macro { name: name.clone(), transformer: Transformer::Rules(Rc::new(rules)) }))
+            },
+
+            _ => new_error!("expected syntax (define-syntax <name> (syntax-rules (<literal>..) (<pattern> <template>)..))").into()
+        }
+    }
+
+
     fn macro_as_function(macro_args: &MacroArgs, body_component: &Rc<Value>) -> Rc<Value> {
         /* Creates an executable function out of a macro */
 
@@ -148,65 +184,70 @@ impl Macro {
 
 
     fn expand_macro(&self, components: &Vec<Rc<Value>>, evaluator: &mut Evaluator) -> Result<Rc<Value>, Error> {
-        /* Expands a macro in an expression */
+        /* Expands one use of a macro, without expanding any macros in the result */
+
+        let expr = Value::cons_list(components);
+
+        let expansion = match &self.transformer {
+            Transformer::Function { args, macro_as_function } => self.call_macro_function(args, macro_as_function, components, evaluator)?,
+            Transformer::Rules(rules) => rules.expand(&expr, evaluator)?
+        };
+
+        if evaluator.trace_expansions() {
+            eprintln!("expanding macro '{}': {} => {}", self.name, expr, expansion);
+        }
+
+        Ok(expansion)
+    }
+
+
+    fn call_macro_function(&self, args: &MacroArgs, macro_as_function: &Rc<Value>, components: &[Rc<Value>], evaluator: &mut Evaluator) -> Result<Rc<Value>, Error> {
+        /* Calls a macro defined by defmacro on its unevaluated arguments */
+
+        let function_args = self.generate_args(args, components[1..].to_vec())?;
 
         // temporarily remove self from the evaluator's macro namespace
         let old_self = evaluator.get_macros().remove(&self.name.name()).unwrap();
 
-        // add macro as function to env
-        evaluator.get_env().insert(self.name.name(), self.macro_as_function.clone());
+        // add macro as function to the env it was defined in, so that it can call itself
+        let env = match &**macro_as_function {
+            Value::Lambda { env, .. } => env.clone(),
+            _ => evaluator.get_globals().clone()
+        };
 
-        let quoted_args = self.generate_quoted_args(components[1..].to_vec())?;
-        let executable_expr = Value::Cons {
-            car: self.name.clone(),
-            cdr: Value::cons_list(&quoted_args)
-        }.rc();
+        env.define(self.name.name(), macro_as_function.clone());
 
-        let evaluation_result = evaluator.eval(&executable_expr)?;
+        let evaluation_result = evaluator.call_function(macro_as_function, function_args);
 
         // remove macro as function from env
-        evaluator.get_env().remove(&self.name.name());
+        env.remove(&self.name.name());
 
         // return self to the macro namespace
         evaluator.get_macros().insert(self.name.name(), old_self);
 
-        Ok(evaluation_result)
+        evaluation_result
     }
 
 
-    fn generate_quoted_args(&self, supplied_args: Vec<Rc<Value>>) -> Result<Vec<Rc<Value>>, Error> {
+    fn generate_args(&self, args: &MacroArgs, supplied_args: Vec<Rc<Value>>) -> Result<Vec<Rc<Value>>, Error> {
         /* list of args suppled to macro -> args to supply to lambda */
 
-        let mut quoted_supplied_args = Vec::with_capacity(supplied_args.len());
-        let quote_name = Value::Name("quote".into()).rc();
-
-        match &self.args {
-            MacroArgs::Finite(_) => {
-                for arg in supplied_args.iter() {
-                    quoted_supplied_args.push(refcount_list![ quote_name.clone(), arg.clone() ]);
-                }
-            },
+        match args {
+            MacroArgs::Finite(_) => Ok(supplied_args),
 
             MacroArgs::Variadic { named_args_count, .. } => {
-                if *named_args_count > supplied_args.len() {
+                // the last named argument is the rest, which may be empty
+                if *named_args_count - 1 > supplied_args.len() {
                     return new_error!("macro '{}' invoked with too few arguments", self.name.name()).into();
                 }
 
-                for i in 0..(*named_args_count - 1) {
-                    quoted_supplied_args.push(refcount_list![ quote_name.clone(), supplied_args[i].clone() ]);
-                }
-
-                let mut quoted_supplied_varargs = vec![ Value::Name("list".into()).rc() ];
+                let mut function_args = supplied_args[..*named_args_count - 1].to_vec();
 
-                for arg in supplied_args[*named_args_count - 1..].iter() {
-                    quoted_supplied_varargs.push(refcount_list![ quote_name.clone(), arg.clone() ]);
-                }
+                function_args.push(Value::cons_list(&supplied_args[*named_args_count - 1..].to_vec()));
 
-                quoted_supplied_args.push(Value::cons_list(&quoted_supplied_varargs));
+                Ok(function_args)
             }
         }
-
-        Ok(quoted_supplied_args)
     }
 }
 
@@ -214,14 +255,25 @@ impl Macro {
 pub fn recursively_expand_macros(expr: &Rc<Value>, evaluator: &mut Evaluator) -> Result<Rc<Value>, Error> {
     /* Expands all macros in an expression */
 
-    if let Some(new_macro) = Macro::parse_macro_definition(expr)? {
+    expand_in_scope(expr, &mut vec![], evaluator)
+}
+
+
+fn expand_in_scope(expr: &Rc<Value>, local_names: &mut Vec<String>, evaluator: &mut Evaluator) -> Result<Rc<Value>, Error> {
+   /* Expands all macros in an expression, within the scope of some local names
+    *
+    * The names bound locally around each expression are tracked, as the
+    * templates of syntax-rules macros defined among them may refer to them.
+    */
+
+    if let Some(new_macro) = Macro::parse_macro_definition(expr, local_names, evaluator)? {
         let new_macro_name = new_macro.name.name();
 
-        if evaluator.get_macros().insert(new_macro_name.clone(), new_macro).is_some() {
-            return new_error!("macro '{}' has already been defined", new_macro_name).into()
-        } else {
-            return Ok(Value::Nil.rc())
+        if evaluator.get_macros().insert(new_macro_name.clone(), new_macro).is_some() && evaluator.warn_macro_redefinition() {
+            eprintln!("Liszp: warning: redefining macro '{}'", new_macro_name);
         }
+
+        return Ok(Value::Nil.rc());
     }
 
     let components = match expr.to_list() {
@@ -229,21 +281,273 @@ pub fn recursively_expand_macros(expr: &Rc<Value>, evaluator: &mut Evaluator) ->
         None => return Ok(expr.clone())
     };
 
-    if components.is_empty() {
+    // quoted expressions are data, so macros within them aren't expanded
+    if components.is_empty() || components[0].name() == "quote" {
         return Ok(expr.clone());
     }
 
+    match components[0].name().as_str() {
+        "quasiquote" => {
+            return match components.as_slice() {
+                [_quasiquote, template] => expand_quasiquote(template, 0, local_names, evaluator),
+                _ => new_error!("quasiquote takes exactly one argument").into()
+            };
+        },
+        "undefmacro" => return undefine_macro(&components, evaluator),
+        "macrolet" => return expand_macrolet(&components, local_names, evaluator),
+        _ => ()
+    }
+
     match evaluator.get_macros().get(&components[0].name()) {
-        Some(m) => m.clone().expand_macro(&components, evaluator),
+        Some(m) => {
+            let expansion = m.clone().expand_macro(&components, evaluator)?;
+
+            // the expansion may itself use macros
+            expand_in_scope(&expansion, local_names, evaluator)
+        },
 
         None => {
             let mut new_components = vec![];
 
-            for comp in components.iter() {
-               new_components.push(recursively_expand_macros(comp, evaluator)?);
+            for i in 0..components.len() {
+                let outer_count = local_names.len();
+
+                local_names.extend(names_bound_in(&components, i, evaluator));
+
+                let expanded = expand_in_scope(&components[i], local_names, evaluator);
+
+                local_names.truncate(outer_count);
+                new_components.push(expanded?);
             }
 
             Ok(Value::cons_list(&new_components))
         }
     }
 }
+
+
+fn names_bound_in(components: &[Rc<Value>], i: usize, evaluator: &mut Evaluator) -> Vec<String> {
+    /* Returns the names which an expression binds locally within its i-th component */
+
+    let mut names = vec![];
+
+    match components[0].name().as_str() {
+        "lambda" if i > 0 && components.len() > 1 => names.extend(SyntaxRules::parameter_names(&components[1])),
+
+        "catch" | "shift" if i > 1 => names.push(components[1].name()),
+
+        "match" if i > 1 => {
+            if let Some((pattern, _)) = components[i].uncons() {
+                if let Ok(pattern) = Pattern::parse(&pattern, evaluator.get_globals()) {
+                    names.extend(pattern.names().into_iter().cloned());
+                }
+            }
+        },
+
+        "restart-case" if i > 1 => {
+            if let Some([_name, params, ..]) = components[i].to_list().as_deref() {
+                names.extend(SyntaxRules::parameter_names(params));
+            }
+        },
+
+        _ => ()
+    }
+
+    names
+}
+
+
+pub fn expand_once(expr: &Rc<Value>, evaluator: &mut Evaluator) -> Result<Option<Rc<Value>>, Error> {
+    /* Expands an expression if it is a use of a macro, returning None otherwise */
+
+    let components = match expr.to_list() {
+        Some(xs) if !xs.is_empty() => xs,
+        _ => return Ok(None)
+    };
+
+    match evaluator.get_macros().get(&components[0].name()) {
+        Some(m) => Ok(Some(m.clone().expand_macro(&components, evaluator)?)),
+        None => Ok(None)
+    }
+}
+
+
+fn undefine_macro(components: &Vec<Rc<Value>>, evaluator: &mut Evaluator) -> Result<Rc<Value>, Error> {
+    /* Removes a macro defined by defmacro or define-syntax, for an expression (undefmacro <name>) */
+
+    match components.as_slice() {
+        [_undefmacro, name] if name.name() != "" => {
+            match evaluator.get_macros().remove(&name.name()) {
+                Some(_) => Ok(Value::Nil.rc()),
+                None => new_error!("cannot undefine '{}' as it is not a macro", name).into()
+            }
+        },
+
+        _ => new_error!("expected syntax (undefmacro <name>)").into()
+    }
+}
+
+
+fn expand_macrolet(components: &Vec<Rc<Value>>, local_names: &mut Vec<String>, evaluator: &mut Evaluator) -> Result<Rc<Value>, Error> {
+   /* Expands a macrolet expression, which defines macros only within its body
+    *
+    * A macrolet expression has the form
+    *
+    *   (macrolet (<definition>..) <body>..)
+    *
+    * where each definition is either ((<name> <args>..) <body>) as in
+    * defmacro, or (<name> (syntax-rules ..)) as in define-syntax. The local
+    * macros shadow any macros with the same names until the body has been
+    * expanded.
+    */
+
+    let (definitions, body) = match components.as_slice() {
+        [_macrolet, definitions, body @ ..] if !body.is_empty() => {
+            match definitions.to_list() {
+                Some(xs) => (xs, body),
+                None => return new_error!("expected a list of macro definitions in macrolet expression").into()
+            }
+        },
+
+        _ => return new_error!("expected syntax (macrolet (<definition>..) <body>..)").into()
+    };
+
+    let mut local_macros = vec![];
+
+    for definition in definitions.iter() {
+        let keyword = match definition.uncons() {
+            Some((name, _)) if name.name() != "" => "define-syntax",
+            Some(_) => "defmacro",
+            None => return new_error!("expected a macro definition in macrolet expression, received '{}'", definition).into()
+        };
+
+        let as_definition = Value::cons(&Value::Name(keyword.into()).rc(), definition).rc();
+
+        match Macro::parse_macro_definition(&as_definition, local_names, evaluator)? {
+            Some(m) => local_macros.push(m),
+            None => unreachable!()
+        }
+    }
+
+    let mut shadowed = vec![];
+
+    for m in local_macros.into_iter() {
+        let name = m.name.name();
+        let previous = evaluator.get_macros().insert(name.clone(), m);
+
+        shadowed.push((name, previous));
+    }
+
+    let body = Value::cons(&Value::Name("do".into()).rc(), &Value::cons_list(&body.to_vec())).rc();
+    let expanded = expand_in_scope(&body, local_names, evaluator);
+
+    // restore the macros the local macros shadowed, in reverse in case a name was defined twice
+    for (name, previous) in shadowed.into_iter().rev() {
+        match previous {
+            Some(m) => evaluator.get_macros().insert(name, m),
+            None => evaluator.get_macros().remove(&name)
+        };
+    }
+
+    expanded
+}
+
+
+fn expand_quasiquote(template: &Rc<Value>, depth: usize, local_names: &mut Vec<String>, evaluator: &mut Evaluator) -> Result<Rc<Value>, Error> {
+   /* Expands a quasiquoted template into an expression which constructs it
+    *
+    * Unquoted parts of the template become part of the expression, so they
+    * are evaluated where the quasiquote appears, and unquote-splicing parts
+    * are appended into the list containing them. A quasiquote within the
+    * template increases the depth, and unquotes only take effect at depth
+    * zero, with deeper ones kept in the result with their depth reduced.
+    */
+
+    let quote = |x: &Rc<Value>| refcount_list![ Value::Name("quote".into()).rc(), x.clone() ];
+    let wrap = |keyword: &str, x: Rc<Value>| refcount_list![ Value::Name("list".into()).rc(), quote(&Value::Name(keyword.into()).rc()), x ];
+
+    let (head, rest) = match template.uncons() {
+        Some(pair) => pair,
+        None => return Ok(quote(template))
+    };
+
+    let argument = match rest.to_list().as_deref() {
+        Some([x]) => Some(x.clone()),
+        _ => None
+    };
+
+    match (head.name().as_str(), argument) {
+        ("unquote", Some(x)) if depth == 0 => return expand_in_scope(&x, local_names, evaluator),
+        ("unquote", Some(x)) => return Ok(wrap("unquote", expand_quasiquote(&x, depth - 1, local_names, evaluator)?)),
+        ("unquote-splicing", Some(_)) if depth == 0 => return new_error!("unquote-splicing ',@' must be within a list").into(),
+        ("unquote-splicing", Some(x)) => return Ok(wrap("unquote-splicing", expand_quasiquote(&x, depth - 1, local_names, evaluator)?)),
+        ("quasiquote", Some(x)) => return Ok(wrap("quasiquote", expand_quasiquote(&x, depth + 1, local_names, evaluator)?)),
+        _ => ()
+    }
+
+    // the list is built by appending runs of ordinary elements and spliced lists
+    let mut segments = vec![ Value::Name("append".into()).rc() ];
+    let mut run = vec![ Value::Name("list".into()).rc() ];
+    let mut cursor = template.clone();
+
+    while let Some((element, rest)) = cursor.uncons() {
+        // the reader keeps the dot of (a . ,b) as a name, and an unquoted element after it is the tail
+        if element.name() == "." && depth == 0 {
+            if let Some([last]) = rest.to_list().as_deref() {
+                if last.uncons().is_some_and(|(keyword, _)| keyword.name() == "unquote") {
+                    cursor = last.clone();
+                    break;
+                }
+            }
+        }
+
+        let spliced = match element.uncons() {
+            Some((keyword, arg)) if keyword.name() == "unquote-splicing" && depth == 0 => arg.to_list().filter(|xs| xs.len() == 1),
+            _ => None
+        };
+
+        match spliced {
+            Some(xs) => {
+                if run.len() > 1 {
+                    segments.push(Value::cons_list(&run));
+                    run.truncate(1);
+                }
+
+                segments.push(expand_in_scope(&xs[0], local_names, evaluator)?);
+            },
+
+            None => run.push(expand_quasiquote(&element, depth, local_names, evaluator)?)
+        }
+
+        cursor = rest;
+
+        // a dotted tail such as `(a . ,b) reads as (a unquote b), so the rest is the tail rather than two elements
+        let is_dotted_unquote = match cursor.uncons() {
+            Some((keyword, arg)) => {
+                matches!(keyword.name().as_str(), "unquote" | "unquote-splicing" | "quasiquote")
+                    && arg.to_list().is_some_and(|xs| xs.len() == 1)
+            },
+
+            None => false
+        };
+
+        if is_dotted_unquote {
+            break;
+        }
+    }
+
+    // an improper list keeps its tail, otherwise the result ends with nil
+    let tail = expand_quasiquote(&cursor, depth, local_names, evaluator)?;
+
+    if segments.len() == 1 && *cursor == Value::Nil {
+        return Ok(Value::cons_list(&run));
+    }
+
+    if run.len() > 1 {
+        segments.push(Value::cons_list(&run));
+    }
+
+    segments.push(tail);
+
+    Ok(Value::cons_list(&segments))
+}
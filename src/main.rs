@@ -3,6 +3,7 @@ mod eval;
 mod macros;
 mod read;
 mod repl;
+mod syntax_rules;
 mod value;
 
 fn main() {
@@ -14,32 +15,86 @@ fn main() {
     }));
 
     let mut filename = None;
+    let mut list_modules = false;
+    let mut stack_limit = None;
+    let mut trace_expansions = false;
+    let mut warn_macro_redefinition = false;
 
-    for arg in std::env::args() {
-        if arg.ends_with(".lzp") {
+    for (i, arg) in std::env::args().enumerate() {
+        if i == 1 && arg == "modules" {
+            list_modules = true;
+        } else if arg.ends_with(".lzp") {
             if let Some(_) = filename {
                 panic!("Liszp: you must provide at most one file");
             } else {
                 filename = Some(arg);
             }
+        } else if let Some(limit) = arg.strip_prefix("--stack-limit=") {
+            match limit.parse::<usize>() {
+                Ok(limit) => stack_limit = Some(limit),
+                Err(_) => panic!("Liszp: --stack-limit expects a number of frames")
+            }
+        } else if arg == "--trace-expansions" {
+            trace_expansions = true;
+        } else if arg == "--warn-macro-redefinition" {
+            warn_macro_redefinition = true;
         }
     }
 
+    let mut evaluator = eval::Evaluator::new();
+
+    if let Some(limit) = stack_limit {
+        evaluator.set_stack_limit(limit);
+    }
+
+    evaluator.set_trace_expansions(trace_expansions);
+    evaluator.set_warn_macro_redefinition(warn_macro_redefinition);
+
+    if list_modules {
+        // liszp modules [file.lzp] lists what the file, or a program in the current directory, can import
+        let project_root = match filename.as_ref().and_then(|fname| std::path::Path::new(fname).parent()) {
+            Some(dir) if dir.as_os_str() != "" => dir.to_path_buf(),
+            _ => std::path::PathBuf::from(".")
+        };
+
+        println!("search path:");
+
+        for dir in eval::search_path(&project_root).iter() {
+            println!("  {}{}", dir.display(), if dir.is_dir() { "" } else { " (missing)" });
+        }
+
+        println!("modules:");
+
+        match evaluator.list_modules(&project_root) {
+            Ok(modules) => {
+                for (name, file, description) in modules.iter() {
+                    println!("  {:<24} {} ({})", name, file.display(), description);
+                }
+            },
+
+            Err(e) => {
+                eprintln!("{}", e.display(false));
+                std::process::exit(1);
+            }
+        }
+
+        return;
+    }
+
     match filename {
         Some(fname) => {
-            let mut evaluator = eval::Evaluator::new();
 
             if let Err(e) = evaluator.load_stdlib() {
                 eprintln!("{}", e.display(false));
-                panic!("fatal error");
+                std::process::exit(1);
             }
 
             if let Err(e) = evaluator.eval_file(fname, false) {
                 eprintln!("{}", e.display(false));
-                panic!("fatal error");
+                std::process::exit(1);
             }
         }
 
-        None => repl::run_repl()
+        None => repl::run_repl(evaluator)
     }
 }
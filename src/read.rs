@@ -1,14 +1,16 @@
 use crate::{
     error::Error,
+    eval::Evaluator,
     new_error,
     refcount_list,
     value::Value
 };
 
+use std::collections::HashMap;
 use std::rc::Rc;
 
 use lazy_static::lazy_static;
-use regex::{ Matches, Regex };
+use regex::Regex;
 use rug;
 
 
@@ -17,31 +19,45 @@ pub struct Reader<'s> {
     line: usize,
     filename: String,
     open_bracket_strings: Vec<&'s str>,
-    token_stream: Matches<'static, 's>,
+    source: &'s str,
+    position: usize,
+    token_regex: &'static Regex,
 }
 
 
 type ReaderResult = Result<Option<Rc<Value>>, Error>;
 
 
+// reads the rest of an expression after a dispatch character, from the
+// reader's stream, returning the value it stands for
+//
+// reader macros defined in liszp by set-dispatch-macro! don't see the stream,
+// they are instead called on the expression which follows the dispatch character
+pub type ReaderMacro = Rc<dyn Fn(&mut Reader, &mut Evaluator) -> ReaderResult>;
+
+
+// maps the dispatch characters of reader macros, which follow a '#', to their functions
+pub type Readtable = HashMap<char, ReaderMacro>;
+
+
 impl<'s> Reader<'s> {
     pub fn new(source: &'s String, filename: &String, permit_leading_amp: bool) -> Self {
         /* Creates a new Reader */
 
         lazy_static! {
             static ref REGEX_WITH_AMP: Regex = Regex::new(concat!(
-                "#.*?\n|",
+                "#.*\n?|",
                 r"0[bB][01_]+|0[xX][0-9a-fA-F_]+|[0-9][0-9_]*|",
                 r"[a-zA-Z_\-\+\*/=<>:\.@&%\?!][a-zA-Z0-9_\-\+\*/=<>:\.@%\&\?!]*|",
-                "\".*?\"|\'.\'|\'|\n|`|,|",
+                "\".*?\"|\'.\'|\'|\n|`|,@|,|",
                 r"\(|\)|\[|\]|\{|\}"
             )).unwrap();
 
             static ref REGEX_WITHOUT_AMP: Regex = Regex::new(concat!(
-                "#.*?\n|",
+                "#.*\n?|",
                 r"0[bB][01_]+|0[xX][0-9a-fA-F_]+|[0-9][0-9_]*|",
                 r"[a-zA-Z_\-\+\*/=<>:\.@%\?!][a-zA-Z0-9_\-\+\*/=<>:\.@%\&\?!]*|",
-                "\".*?\"|\'.\'|\'|\n|`|,|",
+                "\".*?\"|\'.\'|\'|\n|`|,@|,|",
                 r"\(|\)|\[|\]|\{|\}"
             )).unwrap();
         }
@@ -51,16 +67,18 @@ impl<'s> Reader<'s> {
             line: 1,
             filename: filename.clone(),
             open_bracket_strings: vec![],
-            token_stream: if permit_leading_amp {
-                REGEX_WITH_AMP.find_iter(source)
+            source: source.as_str(),
+            position: 0,
+            token_regex: if permit_leading_amp {
+                &REGEX_WITH_AMP
             } else {
-                REGEX_WITHOUT_AMP.find_iter(source)
+                &REGEX_WITHOUT_AMP
             }
         }
     }
 
 
-    fn error_with_reader_position<S: ToString>(&self, msg: S) -> Error {
+    pub fn error_with_reader_position<S: ToString>(&self, msg: S) -> Error {
         /* Creates an error message with the position of the reader */
 
         new_error!(
@@ -73,17 +91,56 @@ impl<'s> Reader<'s> {
     }
 
 
-    pub fn read(&mut self) -> ReaderResult {
+    pub fn peek_char(&self) -> Option<char> {
+        /* Returns the next character of the stream without consuming it */
+
+        self.source[self.position..].chars().next()
+    }
+
+
+    pub fn next_char(&mut self) -> Option<char> {
+        /* Consumes the next character of the stream */
+
+        let c = self.peek_char()?;
+
+        self.position += c.len_utf8();
+
+        if c == '\n' {
+            self.column = 1;
+            self.line += 1;
+        } else {
+            self.column += 1;
+        }
+
+        Some(c)
+    }
+
+
+    fn next_token(&mut self) -> Option<&'s str> {
+        /* Consumes the next token of the stream */
+
+        let source = self.source;
+        let token_match = self.token_regex.find_at(source, self.position)?;
+
+        self.position = token_match.end();
+
+        Some(token_match.as_str())
+    }
+
+
+    pub fn read(&mut self, evaluator: &mut Evaluator) -> ReaderResult {
         /* Reads one value from the stream */
 
-        if let Some(token_match) = self.token_stream.next() {
-            match token_match.as_str() {
-                b @ ("("|"["|"{") => self.read_list(b),
+        if let Some(token) = self.next_token() {
+            match token {
+                b @ ("("|"["|"{") => self.read_list(b, evaluator),
 
                 b @ (")"|"]"|"}") => self.match_closing_bracket(b),
 
+                _ if token.starts_with('#') => self.read_dispatch(token, evaluator),
+
                 "'" => {
-                    match self.read()? {
+                    match self.read(evaluator)? {
                         Some(v) => {
                             let wrapped_expr = refcount_list![ Value::Name("quote".into()).rc(), v ];
 
@@ -95,7 +152,7 @@ impl<'s> Reader<'s> {
                 }
 
                 "`" => {
-                    match self.read()? {
+                    match self.read(evaluator)? {
                         Some(v) => {
                             let wrapped_expr = refcount_list![ Value::Name("quasiquote".into()).rc(), v ];
 
@@ -107,7 +164,7 @@ impl<'s> Reader<'s> {
                 }
 
                 "," => {
-                    match self.read()? {
+                    match self.read(evaluator)? {
                         Some(v) => {
                             let wrapped_expr = refcount_list![ Value::Name("unquote".into()).rc(), v ];
 
@@ -118,7 +175,19 @@ impl<'s> Reader<'s> {
                     }
                 }
 
-                atom => self.read_atom(atom)
+                ",@" => {
+                    match self.read(evaluator)? {
+                        Some(v) => {
+                            let wrapped_expr = refcount_list![ Value::Name("unquote-splicing".into()).rc(), v ];
+
+                            Ok(Some(wrapped_expr))
+                        },
+
+                        None => Ok(None)
+                    }
+                }
+
+                atom => self.read_atom(atom, evaluator)
             }
         } else {
             match self.open_bracket_strings.pop() {
@@ -129,7 +198,66 @@ impl<'s> Reader<'s> {
     }
 
 
-    pub fn read_atom(&mut self, atom: &'s str) -> ReaderResult {
+    fn read_dispatch(&mut self, comment: &'s str, evaluator: &mut Evaluator) -> ReaderResult {
+       /* Reads a use of a reader macro, or skips a comment
+        *
+        * A '#' followed by a space, another '#', a '!' or the end of the line
+        * starts a comment running to the end of the line. Otherwise the
+        * character after the '#' dispatches to the reader macro for it in the
+        * readtable, which is called with the rest of the stream, so that
+        * defining a reader macro never changes how a comment is read.
+        */
+
+        let dispatch_char = match comment[1..].chars().next() {
+            Some(c) if !starts_comment(c) => c,
+            _ => return self.read_atom(comment, evaluator)
+        };
+
+        match evaluator.get_readtable().get(&dispatch_char).cloned() {
+            Some(reader_macro) => {
+                // rewind to just after the dispatch character
+                self.position -= comment.len() - 1 - dispatch_char.len_utf8();
+                self.column += 2;
+
+                reader_macro(self, evaluator)
+            },
+
+            None => {
+                let msg = format!("no reader macro is defined for '#{}' (comments start with '# ')", dispatch_char);
+
+                self.error_with_reader_position(msg).into()
+            }
+        }
+    }
+
+
+    pub fn read_dispatch_argument(&mut self, dispatch_char: char, evaluator: &mut Evaluator) -> ReaderResult {
+       /* Reads the expression a reader macro defined in liszp is applied to
+        *
+        * This is the next expression, or if the dispatch character opens a
+        * list, as in #{..}, the elements up to the closing bracket.
+        */
+
+        match dispatch_char {
+            '(' | '[' | '{' => {
+                let opening_bracket = &self.source[self.position - 1..self.position];
+                let mut list_elements = vec![];
+
+                self.open_bracket_strings.push(opening_bracket);
+
+                while let Some(elem) = self.read(evaluator)? {
+                    list_elements.push(elem);
+                }
+
+                Ok(Some(Value::cons_list(&list_elements)))
+            },
+
+            _ => self.read(evaluator)
+        }
+    }
+
+
+    pub fn read_atom(&mut self, atom: &'s str, evaluator: &mut Evaluator) -> ReaderResult {
         /* Reads an atomic expression */
 
         self.column += atom.len();
@@ -139,7 +267,7 @@ impl<'s> Reader<'s> {
                 self.column = 1;
                 self.line += 1;
 
-                return self.read();
+                return self.read(evaluator);
             },
 
             (_, '0'..='9') => {
@@ -172,7 +300,7 @@ impl<'s> Reader<'s> {
     }
 
 
-    pub fn read_list(&mut self, opening_bracket: &'s str) -> ReaderResult {
+    pub fn read_list(&mut self, opening_bracket: &'s str, evaluator: &mut Evaluator) -> ReaderResult {
         /* Reads a list expression */
 
         self.column += opening_bracket.len();
@@ -180,7 +308,7 @@ impl<'s> Reader<'s> {
 
         let mut list_elements = vec![];
 
-        while let Some(elem) = self.read()? {
+        while let Some(elem) = self.read(evaluator)? {
             list_elements.push(elem);
         }
 
@@ -221,13 +349,51 @@ impl<'s> Reader<'s> {
 }
 
 
-pub fn read(source: &String, filename: &String, reading_stdlib: bool) -> Result<Vec<Rc<Value>>, Error> {
+pub fn starts_comment(c: char) -> bool {
+    /* Returns whether a '#' followed by a character starts a comment rather than a use of a reader macro */
+
+    c.is_whitespace() || c == '#' || c == '!'
+}
+
+
+pub fn default_readtable() -> Readtable {
+    /* Returns the readtable with the reader macros defined by default */
+
+    let mut readtable: Readtable = HashMap::new();
+
+    readtable.insert('|', Rc::new(read_block_comment));
+
+    readtable
+}
+
+
+fn read_block_comment(reader: &mut Reader, evaluator: &mut Evaluator) -> ReaderResult {
+    /* Skips a comment #| .. |# which may span several lines, and reads the expression after it */
+
+    loop {
+        match reader.next_char() {
+            Some('|') if reader.peek_char() == Some('#') => {
+                reader.next_char();
+                break;
+            },
+
+            Some(_) => (),
+
+            None => return reader.error_with_reader_position("unexpected EOF (unclosed comment #| .. |#)").into()
+        }
+    }
+
+    reader.read(evaluator)
+}
+
+
+pub fn read(source: &String, filename: &String, reading_stdlib: bool, evaluator: &mut Evaluator) -> Result<Vec<Rc<Value>>, Error> {
     /* Reads a source string into a vec of values */
 
     let mut reader = Reader::new(source, filename, reading_stdlib);
     let mut values = vec![];
 
-    while let Some(value) = reader.read()? {
+    while let Some(value) = reader.read(evaluator)? {
         values.push(value);
     }
 
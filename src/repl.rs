@@ -2,6 +2,7 @@ use crate::error::Error;
 use crate::eval::Evaluator;
 use crate::new_error;
 use crate::read;
+use crate::refcount_list;
 use crate::value::Value;
 use std::io::Write;
 use std::rc::Rc;
@@ -67,7 +68,7 @@ fn repl_iteration(evaluator: &mut Evaluator) -> Result<Rc<Value>, Error> {
         panic!("cya");
     }
 
-    let exprs = read::read(&input_string, &"<repl>".to_string(), false)?;
+    let exprs = read::read(&input_string, &"<repl>".to_string(), false, evaluator)?;
 
     if exprs.len() == 1 {
         evaluator.eval(&exprs[0])
@@ -77,17 +78,68 @@ fn repl_iteration(evaluator: &mut Evaluator) -> Result<Rc<Value>, Error> {
 }
 
 
-pub fn run_repl() {
+fn choose_restart(evaluator: &mut Evaluator, error: &Error, restarts: &[(String, String)]) -> Option<Rc<Value>> {
+    /* Asks the user which restart to invoke for an unhandled error */
+
+    eprintln!("{}", error.display(false));
+    eprintln!("available restarts:");
+
+    for (i, (name, params)) in restarts.iter().enumerate() {
+        eprintln!("  {}: {} {}", i, name, params);
+    }
+
+    eprintln!("  {}: abort", restarts.len());
+
+    let choice = loop {
+        let input_string = get_line_from_stdin(true).ok()?;
+
+        match input_string.trim().parse::<usize>() {
+            Ok(i) if i <= restarts.len() => break i,
+            _ => eprintln!("expected a number between 0 and {}", restarts.len())
+        }
+    };
+
+    let (name, params) = restarts.get(choice)?;
+
+    let mut invocation = vec![
+        Value::Name("invoke-restart".into()).rc(),
+        refcount_list![ Value::Name("quote".into()).rc(), Value::Name(name.clone()).rc() ]
+    ];
+
+    if params != "()" {
+        eprintln!("arguments for {} {}:", name, params);
+
+        loop {
+            let input_string = get_line_from_stdin(true).ok()?;
+
+            match read::read(&input_string, &"<repl>".to_string(), false, evaluator) {
+                Ok(args) => {
+                    invocation.extend(args);
+                    break;
+                },
+
+                Err(e) => eprintln!("{}", e.display(false))
+            }
+        }
+    }
+
+    Some(Value::cons_list(&invocation))
+}
+
+
+pub fn run_repl(mut evaluator: Evaluator) {
     /* runs a REPL until an exit is reached */
 
-    let mut evaluator = Evaluator::new();
+    evaluator.set_restart_prompt(choose_restart);
 
     if let Err(e) = evaluator.load_stdlib() {
         eprintln!("{}", e.display(false));
     }
 
     loop {
-        match repl_iteration(&mut evaluator) {
+        let displayed = repl_iteration(&mut evaluator).and_then(|v| evaluator.display_value(&v));
+
+        match displayed {
             Ok(v) => println!("{}", v),
             Err(e) => eprintln!("{}", e.display(false))
         }
@@ -0,0 +1,651 @@
+/* A module for pattern-based hygienic macros, defined with syntax-rules
+ *
+ * Names a template binds are renamed, so expansions can't capture the names
+ * of the code using the macro. Other names in a template which refer to
+ * definitions are renamed to aliases bound in the environment the macro was
+ * defined in, so that they refer to those definitions wherever the macro is
+ * used, even where the same names are bound locally. Names of special forms,
+ * builtins and macros are kept as they are, as they can't be rebound.
+ */
+
+use crate::error::Error;
+use crate::eval::{ fresh_name, Environment, Evaluator, Pattern };
+use crate::{ new_error, refcount_list };
+use crate::value::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+
+const ELLIPSIS: &str = "...";
+
+
+// names which are part of the syntax of special forms rather than references to definitions
+const SYNTAX_NAMES: &[&str] = &[
+    "begin", "catch", "def", "defgeneric", "defmacro", "defmethod", "defstruct", "deftype", "define-syntax",
+    "delay", "do", "eval", "export", "finally", "generator", "handler-bind", "if", "import", "lambda",
+    "macrolet", "match", "module", "parameterize", "quasiquote", "quote", "reset", "restart-case", "set!",
+    "shift", "syntax-rules", "try", "undefmacro", "unquote", "unquote-splicing"
+];
+
+
+#[derive(Debug)]
+pub struct SyntaxRules {
+    name: String,
+    literals: Vec<String>,
+    rules: Vec<(Rc<Value>, Rc<Value>)>,
+
+    // the environment the macro was defined in, and the names bound locally there
+    env: Rc<Environment>,
+    local_names: Vec<String>,
+
+    // the aliases bound in env for the names the templates refer to
+    aliases: RefCell<HashMap<String, String>>
+}
+
+
+enum Binding {
+    /* The part of a macro use matched by a pattern variable */
+
+    One(Rc<Value>),
+
+    // a variable followed by an ellipsis, or inside a pattern followed by one
+    Many(Vec<Binding>)
+}
+
+
+impl SyntaxRules {
+
+    /* Parsing */
+
+    pub fn parse(name: &String, spec: &Rc<Value>, env: &Rc<Environment>, local_names: &[String]) -> Result<Self, Error> {
+       /* Parses a syntax-rules specification
+        *
+        * A specification has the form
+        *
+        *   (syntax-rules (<literal>..) (<pattern> <template>)..)
+        *
+        * where the first element of each pattern stands for the macro's name
+        * and is ignored, and literals are names which match only themselves.
+        * The macro is defined in env, where local_names are bound locally.
+        */
+
+        let components = match spec.to_list() {
+            Some(xs) if !xs.is_empty() && xs[0].name() == "syntax-rules" => xs,
+            _ => return new_error!("Liszp: expected (syntax-rules (<literal>..) (<pattern> <template>)..) in definition of '{}'", name).into()
+        };
+
+        let literals = match components.get(1).and_then(|l| l.to_list()) {
+            Some(xs) if xs.iter().all(|x| x.name() != "") => xs.iter().map(|x| x.name()).collect(),
+            _ => return new_error!("Liszp: expected a list of literal names in syntax-rules of '{}'", name).into()
+        };
+
+        let mut rules = vec![];
+
+        for rule in components[2..].iter() {
+            match rule.to_list().as_deref() {
+                Some([pattern, template]) if matches!(&**pattern, Value::Cons { .. }) => {
+                    rules.push((pattern.clone(), template.clone()));
+                },
+
+                _ => return new_error!("Liszp: expected rule (<pattern> <template>) in syntax-rules of '{}', received '{}'", name, rule).into()
+            }
+        }
+
+        Ok(SyntaxRules {
+            name: name.clone(),
+            literals,
+            rules,
+            env: env.clone(),
+            local_names: local_names.to_vec(),
+            aliases: RefCell::new(HashMap::new())
+        })
+    }
+
+
+    /* Expansion */
+
+
+    pub fn expand(&self, expr: &Rc<Value>, evaluator: &mut Evaluator) -> Result<Rc<Value>, Error> {
+        /* Expands a use of the macro with the first rule whose pattern matches it */
+
+        let (_, args) = expr.uncons().unwrap();
+
+        for (pattern, template) in self.rules.iter() {
+            let (_, pattern_args) = pattern.uncons().unwrap();
+            let mut bindings = HashMap::new();
+
+            if self.match_pattern(&pattern_args, &args, &mut bindings) {
+                let binders: Vec<String> = self.binders(template)
+                                               .into_iter()
+                                               .filter(|name| !bindings.contains_key(name))
+                                               .collect();
+
+                let template = self.alias_references(template, &bindings, &binders, evaluator);
+
+                let renames = binders.into_iter()
+                                     .map(|name| (name.clone(), Value::Name(fresh_name(&name)).rc()))
+                                     .collect::<HashMap<String, Rc<Value>>>();
+
+                return self.instantiate(&template, &bindings, &renames);
+            }
+        }
+
+        new_error!("no syntax rule of macro '{}' matches '{}'", self.name, expr).into()
+    }
+
+
+    fn match_pattern(&self, pattern: &Rc<Value>, form: &Rc<Value>, bindings: &mut HashMap<String, Binding>) -> bool {
+        /* Matches a form against a pattern, collecting the bindings of its variables */
+
+        match &**pattern {
+            Value::Name(name) if name == "_" => true,
+
+            Value::Name(name) if self.literals.contains(name) => form.name() == *name,
+
+            Value::Name(name) => {
+                bindings.insert(name.clone(), Binding::One(form.clone()));
+                true
+            },
+
+            Value::Cons { .. } => self.match_list(pattern, form, bindings),
+
+            _ => pattern == form
+        }
+    }
+
+
+    fn match_list(&self, pattern: &Rc<Value>, form: &Rc<Value>, bindings: &mut HashMap<String, Binding>) -> bool {
+        /* Matches a form against a list pattern, which may contain one element followed by an ellipsis */
+
+        let (elements, tail) = Self::split_list(pattern);
+        let (forms, form_tail) = Self::split_list(form);
+
+        let ellipsis = elements.iter().position(|e| e.name() == ELLIPSIS);
+
+        let (before, repeated, after) = match ellipsis {
+            Some(i) if i > 0 => (&elements[..i - 1], Some(&elements[i - 1]), &elements[i + 1..]),
+            _ => (&elements[..], None, &elements[..0])
+        };
+
+        let fixed_count = before.len() + after.len();
+
+        let length_matches = match (repeated, &*tail) {
+            (None, Value::Nil) => forms.len() == fixed_count && *form_tail == Value::Nil,
+            (Some(_), Value::Nil) => forms.len() >= fixed_count && *form_tail == Value::Nil,
+            _ => forms.len() >= fixed_count
+        };
+
+        if !length_matches {
+            return false;
+        }
+
+        for (p, f) in before.iter().zip(forms.iter()) {
+            if !self.match_pattern(p, f, bindings) {
+                return false;
+            }
+        }
+
+        let repeated_count = match (repeated, &*tail) {
+            (Some(_), _) => forms.len() - fixed_count,
+            (None, _) => 0
+        };
+
+        if let Some(repeated) = repeated {
+            let mut iterations = vec![];
+
+            for f in forms[before.len()..before.len() + repeated_count].iter() {
+                let mut iteration_bindings = HashMap::new();
+
+                if !self.match_pattern(repeated, f, &mut iteration_bindings) {
+                    return false;
+                }
+
+                iterations.push(iteration_bindings);
+            }
+
+            for name in self.pattern_variables(repeated) {
+                let many = iterations.iter_mut()
+                                     .map(|b| b.remove(&name).unwrap())
+                                     .collect();
+
+                bindings.insert(name, Binding::Many(many));
+            }
+        }
+
+        let after_start = before.len() + repeated_count;
+
+        for (p, f) in after.iter().zip(forms[after_start..].iter()) {
+            if !self.match_pattern(p, f, bindings) {
+                return false;
+            }
+        }
+
+        match &*tail {
+            Value::Nil => true,
+
+            // a dotted tail matches the forms left over after the fixed elements
+            _ => {
+                let rest = Value::cons_list(&forms[after_start + after.len()..].to_vec());
+                let rest = Self::with_tail(&rest, &form_tail);
+
+                self.match_pattern(&tail, &rest, bindings)
+            }
+        }
+    }
+
+
+    fn pattern_variables(&self, pattern: &Rc<Value>) -> Vec<String> {
+        /* Returns the names of the variables in a pattern */
+
+        match &**pattern {
+            Value::Name(name) if name == "_" || name == ELLIPSIS || name == "." || self.literals.contains(name) => vec![],
+
+            Value::Name(name) => vec![ name.clone() ],
+
+            Value::Cons { .. } => {
+                let (x, xs) = pattern.uncons().unwrap();
+                let mut names = self.pattern_variables(&x);
+
+                names.extend(self.pattern_variables(&xs));
+                names
+            },
+
+            _ => vec![]
+        }
+    }
+
+
+    fn instantiate(&self, template: &Rc<Value>, bindings: &HashMap<String, Binding>, renames: &HashMap<String, Rc<Value>>) -> Result<Rc<Value>, Error> {
+       /* Fills in a template with the parts of a macro use matched by pattern variables
+        *
+        * Names which the template binds are renamed to fresh names, so that
+        * bindings made by the expansion can't capture the user's names.
+        */
+
+        match &**template {
+            Value::Name(name) => {
+                match bindings.get(name) {
+                    Some(Binding::One(value)) => Ok(value.clone()),
+                    Some(Binding::Many(_)) => new_error!("in macro '{}': pattern variable '{}' must be followed by '{}' in template", self.name, name, ELLIPSIS).into(),
+                    None => Ok(renames.get(name).unwrap_or(template).clone())
+                }
+            },
+
+            Value::Cons { .. } => {
+                let elements = template.to_list().unwrap();
+
+                if let [quote, _] = elements.as_slice() {
+                    if quote.name() == "quote" {
+                        return Ok(self.substitute_quoted(template, bindings));
+                    }
+                }
+
+                let mut instantiated = vec![];
+                let mut i = 0;
+
+                while i < elements.len() {
+                    if elements.get(i + 1).is_some_and(|e| e.name() == ELLIPSIS) {
+                        instantiated.extend(self.instantiate_repeated(&elements[i], bindings, renames)?);
+                        i += 2;
+                    } else {
+                        instantiated.push(self.instantiate(&elements[i], bindings, renames)?);
+                        i += 1;
+                    }
+                }
+
+                Ok(Value::cons_list(&instantiated))
+            },
+
+            _ => Ok(template.clone())
+        }
+    }
+
+
+    fn instantiate_repeated(&self, template: &Rc<Value>, bindings: &HashMap<String, Binding>, renames: &HashMap<String, Rc<Value>>) -> Result<Vec<Rc<Value>>, Error> {
+        /* Instantiates a template followed by an ellipsis once for each match of its variables */
+
+        let names: Vec<String> = self.pattern_variables(template)
+                                     .into_iter()
+                                     .filter(|name| matches!(bindings.get(name), Some(Binding::Many(_))))
+                                     .collect();
+
+        let count = match names.first().and_then(|name| bindings.get(name)) {
+            Some(Binding::Many(xs)) => xs.len(),
+            _ => return new_error!("in macro '{}': '{}' follows a template without repeated pattern variables", self.name, ELLIPSIS).into()
+        };
+
+        let mut results = Vec::with_capacity(count);
+
+        for i in 0..count {
+            let mut iteration_bindings = HashMap::new();
+
+            for (name, binding) in bindings.iter() {
+                let binding = match binding {
+                    Binding::Many(xs) if names.contains(name) => {
+                        match xs.get(i) {
+                            Some(x) => x.shallow_clone(),
+                            None => return new_error!("in macro '{}': pattern variables in '{}' matched different numbers of forms", self.name, template).into()
+                        }
+                    },
+
+                    binding => binding.shallow_clone()
+                };
+
+                iteration_bindings.insert(name.clone(), binding);
+            }
+
+            results.push(self.instantiate(template, &iteration_bindings, renames)?);
+        }
+
+        Ok(results)
+    }
+
+
+    fn substitute_quoted(&self, template: &Rc<Value>, bindings: &HashMap<String, Binding>) -> Rc<Value> {
+        /* Fills in pattern variables inside a quoted template without renaming anything */
+
+        match &**template {
+            Value::Name(name) => {
+                match bindings.get(name) {
+                    Some(Binding::One(value)) => value.clone(),
+                    _ => template.clone()
+                }
+            },
+
+            Value::Cons { .. } => {
+                let (x, xs) = template.uncons().unwrap();
+
+                Value::cons(&self.substitute_quoted(&x, bindings), &self.substitute_quoted(&xs, bindings)).rc()
+            },
+
+            _ => template.clone()
+        }
+    }
+
+
+    fn binders(&self, template: &Rc<Value>) -> Vec<String> {
+       /* Returns the names bound by the expressions in a template
+        *
+        * These are the parameters of lambda, defun and restart-case
+        * expressions, the names bound by let, catch, shift and the patterns
+        * of match expressions, and the names defined by def and defun.
+        */
+
+        let elements = match template.to_list() {
+            Some(xs) => xs,
+            None => return vec![]
+        };
+
+        let mut names = vec![];
+
+        match elements.as_slice() {
+            [quote, _] if quote.name() == "quote" => return names,
+
+            [lambda, params, ..] if lambda.name() == "lambda" => names.extend(Self::parameter_names(params)),
+
+            [let_, bindings, ..] if matches!(let_.name().as_str(), "let" | "let*" | "letrec") => {
+                for binding in bindings.to_list().unwrap_or_default().iter() {
+                    if let Some((binder, _)) = binding.uncons() {
+                        Self::collect_names(&binder, &mut names);
+                    }
+                }
+            },
+
+            [keyword, name, ..] if matches!(keyword.name().as_str(), "catch" | "shift" | "def") && name.name() != "" => names.push(name.name()),
+
+            [defun, signature, ..] if defun.name() == "defun" => {
+                if let Some((name, params)) = signature.uncons() {
+                    names.push(name.name());
+                    names.extend(Self::parameter_names(&params));
+                }
+            },
+
+            [match_, _, clauses @ ..] if match_.name() == "match" => {
+                for clause in clauses.iter() {
+                    let pattern = clause.uncons().and_then(|(pattern, _)| Pattern::parse(&pattern, &self.env).ok());
+
+                    if let Some(pattern) = pattern {
+                        names.extend(pattern.names().into_iter().cloned());
+                    }
+                }
+            },
+
+            [restart_case, _, clauses @ ..] if restart_case.name() == "restart-case" => {
+                for clause in clauses.iter() {
+                    if let Some([_name, params, ..]) = clause.to_list().as_deref() {
+                        names.extend(Self::parameter_names(params));
+                    }
+                }
+            },
+
+            _ => ()
+        }
+
+        for element in elements.iter() {
+            names.extend(self.binders(element));
+        }
+
+        names
+    }
+
+
+    pub fn parameter_names(params: &Rc<Value>) -> Vec<String> {
+        /* Returns the names bound by the parameter list of a lambda expression */
+
+        let mut names = vec![];
+        let mut in_required = true;
+
+        for param in params.to_list().unwrap_or_else(|| vec![ params.clone() ]).iter() {
+            if matches!(param.name().as_str(), "?" | ":") {
+                in_required = false;
+            }
+
+            match param.uncons() {
+                // an optional or keyword parameter with a default value
+                Some((name, _)) if !in_required => names.push(name.name()),
+                _ => Self::collect_names(param, &mut names)
+            }
+        }
+
+        names
+    }
+
+
+    fn alias_references(&self, template: &Rc<Value>, variables: &HashMap<String, Binding>, binders: &[String], evaluator: &mut Evaluator) -> Rc<Value> {
+       /* Replaces the names in a template which refer to definitions by aliases for them
+        *
+        * Pattern variables and names bound by the template are left to be
+        * filled in or renamed, as are names which aren't references, such as
+        * those in quoted data, patterns and the names of restarts and types.
+        */
+
+        let alias = |x: &Rc<Value>, evaluator: &mut Evaluator| self.alias_references(x, variables, binders, evaluator);
+
+        let elements = match &**template {
+            Value::Name(name) if !variables.contains_key(name) && !binders.contains(name) && self.refers_to_definition(name, evaluator) => {
+                return Value::Name(self.alias(name)).rc();
+            },
+
+            Value::Cons { .. } => match template.to_list() {
+                Some(xs) => xs,
+                None => {
+                    let (x, xs) = template.uncons().unwrap();
+
+                    return Value::cons(&alias(&x, evaluator), &alias(&xs, evaluator)).rc();
+                }
+            },
+
+            _ => return template.clone()
+        };
+
+        let aliased: Vec<Rc<Value>> = match (elements[0].name().as_str(), elements.as_slice()) {
+            ("quote" | "defstruct" | "deftype" | "defgeneric" | "module" | "import", _) => return template.clone(),
+
+            ("quasiquote", [quasiquote, quoted]) => vec![ quasiquote.clone(), self.alias_unquoted(quoted, variables, binders, evaluator) ],
+
+            ("defmethod", [defmethod, name, params, body @ ..]) => {
+                let mut aliased = vec![ defmethod.clone(), name.clone(), params.clone() ];
+
+                aliased.extend(body.iter().map(|x| alias(x, evaluator)));
+                aliased
+            },
+
+            // the first element of each clause is a pattern in a match expression, and a name in a restart-case
+            ("match" | "restart-case", [keyword, expr, clauses @ ..]) => {
+                let mut aliased = vec![ keyword.clone(), alias(expr, evaluator) ];
+
+                for clause in clauses.iter() {
+                    match clause.to_list().as_deref() {
+                        Some([first, rest @ ..]) => {
+                            let mut aliased_clause = vec![ first.clone() ];
+
+                            aliased_clause.extend(rest.iter().map(|x| alias(x, evaluator)));
+                            aliased.push(Value::cons_list(&aliased_clause));
+                        },
+
+                        _ => aliased.push(clause.clone())
+                    }
+                }
+
+                aliased
+            },
+
+            _ => elements.iter().map(|x| alias(x, evaluator)).collect()
+        };
+
+        Value::cons_list(&aliased)
+    }
+
+
+    fn alias_unquoted(&self, template: &Rc<Value>, variables: &HashMap<String, Binding>, binders: &[String], evaluator: &mut Evaluator) -> Rc<Value> {
+        /* Aliases the references in the unquoted parts of a quasiquoted template */
+
+        match template.to_list().as_deref() {
+            Some([unquote, x]) if matches!(unquote.name().as_str(), "unquote" | "unquote-splicing") => {
+                refcount_list![ unquote.clone(), self.alias_references(x, variables, binders, evaluator) ]
+            },
+
+            _ => match template.uncons() {
+                Some((x, xs)) => {
+                    let x = self.alias_unquoted(&x, variables, binders, evaluator);
+                    let xs = self.alias_unquoted(&xs, variables, binders, evaluator);
+
+                    Value::cons(&x, &xs).rc()
+                },
+
+                None => template.clone()
+            }
+        }
+    }
+
+
+    fn refers_to_definition(&self, name: &String, evaluator: &mut Evaluator) -> bool {
+        /* Returns whether a name in a template refers to a definition, rather than being syntax */
+
+        !matches!(name.as_str(), "." | "_" | "?" | "@" | ELLIPSIS)
+            && !name.starts_with(':')
+            && !SYNTAX_NAMES.contains(&name.as_str())
+            && !Evaluator::is_builtin(name)
+            && !evaluator.get_macros().contains_key(name)
+            && !self.local_names.contains(name)
+    }
+
+
+    fn alias(&self, name: &String) -> String {
+        /* Returns the alias for a name in the environment the macro was defined in, binding it the first time */
+
+        self.aliases.borrow_mut()
+                    .entry(name.clone())
+                    .or_insert_with(|| {
+                        let alias = fresh_name(name);
+
+                        self.env.define_alias(&alias, name);
+                        alias
+                    })
+                    .clone()
+    }
+
+
+    fn collect_names(params: &Rc<Value>, names: &mut Vec<String>) {
+        /* Collects the names in a parameter list or destructuring pattern */
+
+        match &**params {
+            Value::Name(name) if !matches!(name.as_str(), "." | "_" | "?" | ":" | "@" | ELLIPSIS) => names.push(name.clone()),
+
+            Value::Cons { .. } => {
+                let (x, xs) = params.uncons().unwrap();
+
+                Self::collect_names(&x, names);
+                Self::collect_names(&xs, names);
+            },
+
+            _ => ()
+        }
+    }
+
+
+    /* Lists */
+
+
+    fn split_list(xs: &Rc<Value>) -> (Vec<Rc<Value>>, Rc<Value>) {
+        /* Splits a pattern into its elements and its tail, which may be written with '.' */
+
+        let mut elements = vec![];
+        let mut cursor = xs.clone();
+
+        while let Some((x, rest)) = cursor.uncons() {
+            elements.push(x);
+            cursor = rest;
+        }
+
+        match elements.as_slice() {
+            [.., dot, tail] if dot.name() == "." && *cursor == Value::Nil => {
+                let tail = tail.clone();
+
+                elements.truncate(elements.len() - 2);
+
+                (elements, tail)
+            },
+
+            _ => (elements, cursor)
+        }
+    }
+
+
+    fn with_tail(xs: &Rc<Value>, tail: &Rc<Value>) -> Rc<Value> {
+        /* Replaces the nil at the end of a list with tail */
+
+        if **tail == Value::Nil {
+            return xs.clone();
+        }
+
+        let mut elements = vec![];
+        let mut cursor = xs.clone();
+
+        while let Some((x, rest)) = cursor.uncons() {
+            elements.push(x);
+            cursor = rest;
+        }
+
+        let mut result = tail.clone();
+
+        for x in elements.iter().rev() {
+            result = Value::cons(x, &result).rc();
+        }
+
+        result
+    }
+}
+
+
+impl Binding {
+    fn shallow_clone(&self) -> Self {
+        /* Copies a binding, sharing the values it contains */
+
+        match self {
+            Binding::One(value) => Binding::One(value.clone()),
+            Binding::Many(xs) => Binding::Many(xs.iter().map(|x| x.shallow_clone()).collect())
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use crate::eval::{ Continuation, Environment, Generator, GenericFunction, Parameter, Promise, Signature, StructOperation, StructType, Syntax };
+use std::cell::RefCell;
 use std::rc::Rc;
 
 
@@ -13,10 +15,7 @@ macro_rules! refcount_list {
             )*
 
             for ex in expr_list.iter() {
-                cons = Rc::new(Value::Cons {
-                    car: Rc::clone(ex),
-                    cdr: cons
-                });
+                cons = Value::cons(ex, &cons).rc();
             }
 
             cons
@@ -29,26 +28,57 @@ macro_rules! refcount_list {
 pub enum Value {
     Bool(bool),
 
+    Box(RefCell<Rc<Value>>),
+
     Cons {
-        car: Rc<Value>,
-        cdr: Rc<Value>
+        car: RefCell<Rc<Value>>,
+        cdr: RefCell<Rc<Value>>
+    },
+
+    Continuation(Rc<Continuation>),
+
+    Error {
+        message: String,
+        payload: Rc<Value>
     },
 
     Float(rug::Float),
 
+    Generator(Rc<Generator>),
+
+    Generic(Rc<GenericFunction>),
+
     Integer(rug::Integer),
 
     Lambda {
-        args: Vec<String>,
+        signature: Rc<Signature>,
         body: Rc<Value>,
+        env: Rc<Environment>,
         name: Option<String>
     },
 
     Name(String),
 
+    Parameter(Rc<Parameter>),
+
+    Promise(Rc<Promise>),
+
     Nil,
     
     String(String),
+
+    Struct {
+        kind: Rc<StructType>,
+        fields: Vec<RefCell<Rc<Value>>>
+    },
+
+    StructFunction {
+        kind: Rc<StructType>,
+        operation: StructOperation,
+        name: String
+    },
+
+    Syntax(Rc<Syntax>)
 }
 
 
@@ -67,6 +97,42 @@ impl Value {
     }
 
 
+    pub fn type_name(&self) -> String {
+        /* Returns the name of the type of the value, as given by type-of */
+
+        let type_name = match self {
+            Value::Bool(_) => "bool",
+            Value::Box(_) => "box",
+            Value::Cons { .. } => "cons",
+            Value::Continuation(_) => "continuation",
+            Value::Error { .. } => "error",
+            Value::Float(_) => "float",
+            Value::Generator(_) => "generator",
+            Value::Integer(_) => "int",
+            Value::Generic(_) | Value::Lambda { .. } | Value::StructFunction { .. } => "function",
+            Value::Name(_) => "name",
+            Value::Nil => "nil",
+            Value::Parameter(_) => "parameter",
+            Value::Promise(_) => "promise",
+            Value::String(_) => "str",
+            Value::Struct { kind, .. } => kind.type_name(),
+            Value::Syntax(_) => "syntax"
+        };
+
+        type_name.to_string()
+    }
+
+
+    pub fn uncons(&self) -> Option<(Rc<Value>, Rc<Value>)> {
+        /* if self is a cons pair then Some((car, cdr)) else None */
+
+        match self {
+            Value::Cons { car, cdr } => Some((car.borrow().clone(), cdr.borrow().clone())),
+            _ => None
+        }
+    }
+
+
     pub fn rc(self) -> Rc<Value> {
         /* Value -> Rc<Value> */
     
@@ -81,13 +147,13 @@ impl Value {
             return Some(vec![]);
         }
 
-        let mut cursor = self;
+        let mut cursor = self.uncons();
         let mut count = 0;
         let mut list = vec![];
 
-        while let Value::Cons { car, cdr } = cursor {
-            list.push(car.clone());
-            cursor = &cdr;
+        while let Some((car, cdr)) = cursor {
+            list.push(car);
+            cursor = cdr.uncons();
 
             count += 1;
         }
@@ -106,8 +172,8 @@ impl Value {
         /* Creates a cons pair */
 
         Value::Cons {
-            car: car.clone(),
-            cdr: cdr.clone()
+            car: RefCell::new(car.clone()),
+            cdr: RefCell::new(cdr.clone())
         }
     }
 
@@ -125,18 +191,50 @@ impl Value {
     }
 
 
+    fn atoms_are_equal(x: &Value, y: &Value) -> bool {
+        /* Compares two values which aren't both cons pairs */
+
+        match (x, y) {
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Box(a), Value::Box(b)) => std::ptr::eq(a, b),
+            (Value::Continuation(a), Value::Continuation(b)) => Rc::ptr_eq(a, b),
+            (Value::Float(a), Value::Float(b)) => a == b,
+            (Value::Generator(a), Value::Generator(b)) => Rc::ptr_eq(a, b),
+            (Value::Generic(a), Value::Generic(b)) => Rc::ptr_eq(a, b),
+            (Value::Integer(a), Value::Integer(b)) => a == b,
+            (Value::Name(a), Value::Name(b)) => a == b,
+            (Value::Nil, Value::Nil) => true,
+            (Value::Parameter(a), Value::Parameter(b)) => Rc::ptr_eq(a, b),
+            (Value::Promise(a), Value::Promise(b)) => Rc::ptr_eq(a, b),
+            (Value::String(a), Value::String(b)) => a == b,
+
+            (Value::Struct { kind: a, fields: xs }, Value::Struct { kind: b, fields: ys }) => {
+                Rc::ptr_eq(a, b) && xs.iter().zip(ys.iter()).all(|(x, y)| x == y)
+            },
+
+            (Value::StructFunction { kind: a, name: x, .. }, Value::StructFunction { kind: b, name: y, .. }) => {
+                Rc::ptr_eq(a, b) && x == y
+            },
+
+            _ => false
+        }
+    }
+
+
     fn print_list(xs: &Value) -> String {
         let mut string = String::new();
-        let mut cursor = xs;
+        let mut cursor = xs.uncons();
+        let mut tail = Value::Nil.rc();
 
-        while let Value::Cons { car, cdr } = cursor {
-            string = format!("{}{} ", string, car);
-            cursor = cdr;
+        while let Some((car, cdr)) = cursor {
+            string.push_str(&format!("{} ", car));
+            cursor = cdr.uncons();
+            tail = cdr;
         }
 
-        match cursor {
+        match &*tail {
             Value::Nil => string = string[..string.len() - 1].to_string(),
-            _ => string = format!("{} . {}", string, cursor)
+            _ => string = format!("{}. {}", string, tail)
         }
 
         string
@@ -149,10 +247,20 @@ impl std::fmt::Display for Value {
         return write!(f, "{}", match self {
             Value::Bool(b) => format!("{}", b),
 
+            Value::Box(v) => format!("<box {}>", v.borrow()),
+
             Value::Cons { .. } => format!("({})", Value::print_list(self)),
 
+            Value::Continuation(_) => "<continuation>".into(),
+
+            Value::Error { message, .. } => format!("<error: {}>", message),
+
             Value::Float(f) => format!("{}", f),
 
+            Value::Generator(_) => "<generator>".into(),
+
+            Value::Generic(generic) => format!("<generic function '{}'>", generic.name),
+
             Value::Integer(i) => format!("{}", i),
 
             Value::Lambda { name, .. } => {
@@ -166,7 +274,37 @@ impl std::fmt::Display for Value {
 
             Value::Nil => "nil".into(),
 
-            Value::String(s) => format!("{}", s)
+            Value::Parameter(_) => "<parameter>".into(),
+
+            Value::Promise(_) => "<promise>".into(),
+
+            Value::String(s) => s.clone(),
+
+            Value::Struct { kind, fields } if kind.sum.is_some() => {
+                if fields.is_empty() {
+                    kind.name.clone()
+                } else {
+                    let mut components = vec![ kind.name.clone() ];
+
+                    components.extend(fields.iter().map(|value| value.borrow().to_string()));
+
+                    format!("({})", components.join(" "))
+                }
+            },
+
+            Value::Struct { kind, fields } => {
+                let mut components = vec![ kind.constructor_name() ];
+
+                for (field, value) in kind.fields.iter().zip(fields.iter()) {
+                    components.push(format!(":{} {}", field, value.borrow()));
+                }
+
+                format!("({})", components.join(" "))
+            },
+
+            Value::StructFunction { name, .. } => format!("<function '{}'>", name),
+
+            Value::Syntax(syntax) => syntax.source().to_string()
         });
     }
 }
@@ -174,16 +312,43 @@ impl std::fmt::Display for Value {
 
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (Value::Bool(a), Value::Bool(b)) => a == b,
-            (Value::Cons { car: a, cdr: x}, Value::Cons { car: b, cdr: y }) => {
-                a == b && x == y
-            },
-            (Value::Float(a), Value::Float(b)) => a == b,
-            (Value::Integer(a), Value::Integer(b)) => a == b,
-            (Value::Name(a), Value::Name(b)) => a == b,
-            (Value::Nil, Value::Nil) => true,
-            _ => false
+        let (mut xs, mut ys) = match (self.uncons(), other.uncons()) {
+            (Some((a, x)), Some((b, y))) if a == b => (x, y),
+            (None, None) => return Value::atoms_are_equal(self, other),
+            _ => return false
+        };
+
+        // walks down the cdrs of lists iteratively so that comparing long lists can't overflow the stack
+        loop {
+            match (xs.uncons(), ys.uncons()) {
+                (Some((a, x)), Some((b, y))) if a == b => {
+                    xs = x;
+                    ys = y;
+                },
+
+                (None, None) => return Value::atoms_are_equal(&xs, &ys),
+
+                _ => return false
+            }
+        }
+    }
+}
+
+
+impl Drop for Value {
+    fn drop(&mut self) {
+        /* Drops the cdrs of a list iteratively so that dropping long lists can't overflow the stack */
+
+        let mut next = match self {
+            Value::Cons { cdr, .. } => std::mem::replace(cdr.get_mut(), Rc::new(Value::Nil)),
+            _ => return
+        };
+
+        while let Ok(mut value) = Rc::try_unwrap(next) {
+            next = match &mut value {
+                Value::Cons { cdr, .. } => std::mem::replace(cdr.get_mut(), Rc::new(Value::Nil)),
+                _ => return
+            };
         }
     }
 }
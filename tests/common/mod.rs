@@ -0,0 +1,109 @@
+#![allow(dead_code)]
+
+use std::path::{ Path, PathBuf };
+use std::process::Command;
+use std::sync::atomic::{ AtomicUsize, Ordering };
+
+
+// numbers the scratch directories of the tests in a test binary
+static SCRATCH_DIRS: AtomicUsize = AtomicUsize::new(0);
+
+
+pub struct Output {
+    /* What a liszp program printed */
+
+    pub stdout: String,
+    pub stderr: String
+}
+
+
+/* Running programs */
+
+
+pub fn run(source: &str) -> Output {
+    /* Runs a liszp program with the standard library */
+
+    let dir = scratch_dir(&[ ("main.lzp", source) ]);
+    let output = run_file(&dir.join("main.lzp"), &[]);
+
+    let _ = std::fs::remove_dir_all(&dir);
+
+    output
+}
+
+
+pub fn run_file(path: &Path, env: &[(&str, &str)]) -> Output {
+   /* Runs a liszp file from the directory it is in
+    *
+    * The program is run outside the repository so that it only finds the
+    * standard library through the interpreter's own search.
+    */
+
+    run_liszp(Path::new(env!("CARGO_BIN_EXE_liszp")), &[ path.to_str().unwrap() ], path.parent().unwrap(), env)
+}
+
+
+pub fn run_liszp(executable: &Path, args: &[&str], dir: &Path, env: &[(&str, &str)]) -> Output {
+    /* Runs a liszp executable with some arguments and environment variables from a directory */
+
+    let output = Command::new(executable)
+                         .args(args)
+                         .current_dir(dir)
+                         .env_remove("LISZP_PATH")
+                         .env_remove("LISZP_STDLIB")
+                         .envs(env.iter().cloned())
+                         .output()
+                         .expect("failed to run liszp");
+
+    Output {
+        stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+        stderr: String::from_utf8_lossy(&output.stderr).to_string()
+    }
+}
+
+
+pub fn scratch_dir(files: &[(&str, &str)]) -> PathBuf {
+    /* Creates a fresh directory holding some files, given by paths relative to it */
+
+    let n = SCRATCH_DIRS.fetch_add(1, Ordering::SeqCst);
+    let test_binary = std::env::current_exe().unwrap();
+    let test_name = test_binary.file_stem().unwrap().to_string_lossy().to_string();
+    let dir = std::env::temp_dir().join(format!("liszp-{}-{}-{}", test_name, std::process::id(), n));
+
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    for (path, contents) in files.iter() {
+        let file = dir.join(path);
+
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::write(&file, contents).unwrap();
+    }
+
+    dir
+}
+
+
+/* Assertions */
+
+
+pub fn assert_prints(source: &str, expected: &str) {
+    /* Asserts that a program runs without error and prints some lines */
+
+    let output = run(source);
+
+    assert_eq!(output.stderr, "", "unexpected error running:\n{}", source);
+    assert_eq!(output.stdout.trim_end(), expected.trim_end(), "unexpected output running:\n{}", source);
+}
+
+
+pub fn assert_fails(source: &str, message: &str) {
+    /* Asserts that a program stops with an uncaught error containing a message */
+
+    let output = run(source);
+
+    assert!(
+        output.stderr.contains(message),
+        "expected an error containing '{}' running:\n{}\nstderr was:\n{}", message, source, output.stderr
+    );
+}
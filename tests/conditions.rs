@@ -0,0 +1,128 @@
+mod common;
+
+use common::{ assert_fails, assert_prints };
+
+
+/* Restarts */
+
+
+#[test]
+fn invoking_a_restart_returns_from_restart_case() {
+    assert_prints(
+        "(println (restart-case (+ 1 (invoke-restart 'use-value 41)) (use-value (v) v)))
+         (println (restart-case (+ 1 (invoke-restart 'r :k 5)) (r (: [k 2]) (* k 10))))",
+        "41\n50"
+    );
+}
+
+
+#[test]
+fn restarts_unwind_through_finally() {
+    assert_prints(
+        "(println (restart-case
+             (try (invoke-restart 'skip) (finally (println 'finally-ran)))
+             (skip () 'skipped)))",
+        "finally-ran\nskipped"
+    );
+}
+
+
+#[test]
+fn compute_restarts_lists_the_active_restarts() {
+    assert_prints(
+        "(println (restart-case (restart-case (compute-restarts) (a () 1) (b (x) 2)) (c () 3)))",
+        "(a b c)"
+    );
+}
+
+
+#[test]
+fn invoking_an_inactive_restart_is_an_error() {
+    assert_prints(
+        "(println (try (invoke-restart 'nope) (catch e (error-message e))))",
+        "\"no restart named 'nope' is active\""
+    );
+}
+
+
+/* Handlers */
+
+
+#[test]
+fn handlers_run_before_unwinding() {
+    assert_prints(
+        "(println (restart-case
+             (handler-bind ((lambda (c) (invoke-restart 'use-value (error-message c))))
+                 (+ 1 (error \"bad\")))
+             (use-value (v) (list 'recovered v))))",
+        "(recovered \"bad\")"
+    );
+}
+
+
+#[test]
+fn declining_handlers_let_the_error_unwind() {
+    assert_prints(
+        "(def declined (box 0))
+         (println (try
+             (handler-bind ((lambda (c) (set-box! declined 1))) (error \"x\"))
+             (catch e (list 'caught (error-message e) (unbox declined)))))",
+        "(caught \"x\" 1)"
+    );
+}
+
+
+#[test]
+fn a_closer_try_catches_before_outer_handlers() {
+    assert_prints(
+        "(defun (parse x) (restart-case (if (int? x) x (error \"not a number\")) (use-value (v) v)))
+         (println (handler-bind ((lambda (c) (invoke-restart 'use-value 0)))
+             (try (parse 'a) (catch e \"caught-by-try\"))))
+         (println (try
+             (handler-bind ((lambda (c) (invoke-restart 'use-value 0))) (parse 'a))
+             (catch e \"caught-by-try\")))",
+        "\"caught-by-try\"\n0"
+    );
+}
+
+
+#[test]
+fn try_does_not_hide_signals_from_outer_handlers() {
+    assert_prints(
+        "(println (handler-bind ((lambda (c) (println (list 'saw c))))
+             (try (signal 'hi) (catch e 'no))
+             'done))",
+        "(saw hi)\ndone"
+    );
+}
+
+
+#[test]
+fn handlers_signalling_reach_only_outer_handlers() {
+    assert_prints(
+        "(println (handler-bind ((lambda (c) (println (list 'outer c))))
+             (handler-bind ((lambda (c) (println (list 'inner c)) (signal 'nested)))
+                 (signal 'hi)
+                 'done)))",
+        "(inner hi)\n(outer nested)\n(outer hi)\ndone"
+    );
+}
+
+
+#[test]
+fn handlers_may_be_any_function() {
+    assert_prints(
+        "(defun (parse x) (restart-case (if (int? x) x (error \"not a number\")) (use-value (v) v)))
+         (defgeneric on-error (c))
+         (defmethod on-error ((c : error)) (invoke-restart 'use-value 7))
+         (println (handler-bind (on-error) (parse 'a)))
+         (println (error-message (call/cc (lambda (k) (handler-bind (k) (parse 'b))))))",
+        "7\n\"not a number\""
+    );
+}
+
+
+#[test]
+fn handlers_must_be_functions() {
+    assert_fails("(handler-bind (1) 2)", "handler-bind expected functions as handlers, received '1'");
+}
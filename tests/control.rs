@@ -0,0 +1,181 @@
+mod common;
+
+use common::assert_prints;
+
+
+/* Continuations */
+
+
+#[test]
+fn call_cc_escapes() {
+    assert_prints(
+        "(println (+ 1 (call/cc (lambda (k) (+ 10 (k 5))))))
+         (defun (find-first pred xs)
+           (call/cc (lambda (return)
+             (def loop (lambda (ys) (if (nil? ys) nil (do (if (pred (car ys)) (return (car ys)) nil) (loop (cdr ys))))))
+             (loop xs))))
+         (println (find-first (lambda (x) (> x 3)) (list 1 2 5 7)))
+         (println (call/cc (lambda (k) k)))",
+        "6\n5\n<continuation>"
+    );
+}
+
+
+#[test]
+fn continuations_can_be_reentered() {
+    assert_prints(
+        "(def saved (box nil))
+         (def count (box 0))
+         (do
+           (println (+ 100 (call/cc (lambda (k) (set-box! saved k) 0))))
+           (set-box! count (+ 1 (unbox count)))
+           (if (< (unbox count) 3) ((unbox saved) (unbox count)) nil))",
+        "100\n101\n102"
+    );
+}
+
+
+#[test]
+fn shift_captures_up_to_reset() {
+    assert_prints(
+        "(println (reset (+ 1 (shift k (k (k 10))))))
+         (println (reset (list 1 (shift k 'escaped) 3)))
+         (println (* 2 (reset (+ 1 (shift k (+ (k 1) (k 2)))))))
+         (println (try (shift k 1) (catch e (error-message e))))",
+        "12\nescaped\n10\n\"Liszp: shift expression used outside of a reset expression\""
+    );
+}
+
+
+/* Generators */
+
+
+#[test]
+fn generators_yield_values_lazily() {
+    assert_prints(
+        "(defun (naturals) (generator (def loop (lambda (n) (do (yield n) (loop (+ n 1))))) (loop 0)))
+         (println (take 5 (naturals)))
+         (println (take 4 (map (filter (naturals) (lambda (x) (== 0 (% x 2)))) (lambda (x) (* x x)))))
+         (defun (walk tree) (if (cons? tree) (do (walk (car tree)) (walk (cdr tree))) (if (nil? tree) nil (yield tree))))
+         (println (generator->list (generator (walk '((1 2) (3 (4 5)) 6)))))",
+        "(0 1 2 3 4)\n(0 4 16 36)\n(1 2 3 4 5 6)"
+    );
+}
+
+
+#[test]
+fn generators_receive_values_when_resumed() {
+    assert_prints(
+        "(def g (generator (def a (yield 1)) (def b (yield (+ a 10))) (yield (+ b 100))))
+         (println (list (g) (g 5) (g 7) (g) (generator-done? g) (g)))",
+        "(1 15 107 nil true nil)"
+    );
+}
+
+
+#[test]
+fn generator_errors() {
+    assert_prints(
+        "(println (try (yield 1) (catch e (error-message e))))
+         (def bad (generator (yield 1) (error \"boom\")))
+         (println (list (bad) (try (bad) (catch e (error-message e))) (generator-done? bad)))
+         (def self-ref (generator (self-ref)))
+         (println (try (self-ref) (catch e (error-message e))))",
+        "\"Liszp: yield used outside of a generator\"\n(1 \"boom\" true)\n\"generator was called while it was already running\""
+    );
+}
+
+
+#[test]
+fn generators_left_by_continuations_are_done() {
+    assert_prints(
+        "(def leave nil)
+         (def g (generator (yield 1) (leave 'left) (yield 2)))
+         (println (list (g) (call/cc (lambda (k) (set! leave k) (g))) (generator-done? g) (g)))
+         (def h (generator (shift k 'captured) (yield 1)))
+         (println (list (reset (h)) (generator-done? h) (h)))",
+        "(1 left true nil)\n(captured true nil)"
+    );
+}
+
+
+/* Lazy evaluation */
+
+
+#[test]
+fn promises_are_forced_once() {
+    assert_prints(
+        "(def count (box 0))
+         (def p (delay (do (set-box! count (+ 1 (unbox count))) (* 6 7))))
+         (println (list (promise? p) (type-of p) p (unbox count)))
+         (println (list (force p) (force p) (unbox count)))
+         (println (list (force (make-promise 5)) (equals? p (make-promise p)) (force 3)))
+         (def fails (delay (raise \"boom\")))
+         (println (list (try (force fails) (catch e e)) (try (force fails) (catch e e))))",
+        "(true promise <promise> 0)\n(42 42 1)\n(5 true 3)\n(\"boom\" \"boom\")"
+    );
+}
+
+
+#[test]
+fn streams_are_evaluated_as_far_as_needed() {
+    assert_prints(
+        "(def naturals (stream-from 0 1))
+         (println (stream-take 5 naturals))
+         (println (stream-take 5 (stream-map naturals (lambda (x) (* x x)))))
+         (println (stream-take 5 (stream-filter naturals (lambda (x) (== 0 (% x 3))))))
+         (println (stream-take 10 (stream-cons 1 (stream-cons 2 nil))))
+         (defun (fibs a b) (stream-cons a (fibs b (+ a b))))
+         (println (stream-take 10 (fibs 0 1)))
+         (println (car (stream-rest (stream-rest (stream-from 0 5)))))",
+        "(0 1 2 3 4)\n(0 1 4 9 16)\n(0 3 6 9 12)\n(1 2)\n(0 1 1 2 3 5 8 13 21 34)\n10"
+    );
+}
+
+
+/* Dynamic variables */
+
+
+#[test]
+fn parameterize_rebinds_within_its_extent() {
+    assert_prints(
+        "(defparameter indent 0)
+         (def radix (make-parameter 10))
+         (defun (show) (list (indent) (radix)))
+         (println (list (show) (type-of radix) radix))
+         (println (parameterize ([indent 4] [radix 16]) (show)))
+         (println (show))
+         (println (parameterize ([indent 1]) (parameterize ([indent (+ 1 (indent))]) (show))))",
+        "((0 10) parameter <parameter>)\n(4 16)\n(0 10)\n(2 10)"
+    );
+}
+
+
+#[test]
+fn parameterize_is_undone_by_non_local_exits() {
+    assert_prints(
+        "(defparameter indent 0)
+         (println (try (parameterize ([indent 99]) (raise \"oops\")) (catch e (list e (indent)))))
+         (println (parameterize ([indent 7]) (+ 0 (call/cc (lambda (c) (indent))))))
+         (def g (generator (parameterize ([indent 3]) (yield (indent)) (yield (indent)))))
+         (println (list (g) (indent) (g)))
+         (println (try (parameterize ([5 1]) 1) (catch e (error-message e))))",
+        "(\"oops\" 0)\n7\n(3 0 3)\n\"parameterize expected a parameter, received '5'\""
+    );
+}
+
+
+#[test]
+fn parameterize_bindings_are_restored_by_reentered_continuations() {
+    assert_prints(
+        "(defparameter indent 0)
+         (def saved nil)
+         (def count (box 0))
+         (println (parameterize ([indent 5]) (+ (call/cc (lambda (k) (set! saved k) 0)) (indent))))
+         (set-box! count (+ 1 (unbox count)))
+         (if (< (unbox count) 2) (saved 10) nil)
+         (println (indent))
+         (println (reset (parameterize ([indent 2]) (+ (shift k (parameterize ([indent 9]) (k 0))) (indent)))))",
+        "5\n15\n0\n2"
+    );
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use common::{ assert_fails, assert_prints, run };
+
+
+/* try, catch and finally */
+
+
+#[test]
+fn catch_receives_user_and_internal_errors() {
+    assert_prints(
+        "(println (try (car 5) (catch e (list 'caught (error? e)))))
+         (println (try (error \"bad thing\" 42) (catch e (error-payload e))))
+         (println (try (raise 'oops) (catch e e)))",
+        "(caught true)\n42\noops"
+    );
+}
+
+
+#[test]
+fn error_messages_are_strings_like_any_other() {
+    assert_prints(
+        "(println (try (error \"bad thing\") (catch e (error-message e))))
+         (println (try (car 5) (catch e (error-message e))))
+         (println (equals? \"bad thing\" (try (error \"bad thing\") (catch e (error-message e)))))
+         (println (try (error \"x\") (catch e e)))",
+        "\"bad thing\"\n\"Liszp: function 'car' expected to receive cons pair\"\ntrue\n<error: x>"
+    );
+}
+
+
+#[test]
+fn uncaught_errors_show_the_message_without_quotes() {
+    let output = run("(error \"improperly formatted input\")");
+
+    assert!(output.stderr.contains("\nimproperly formatted input\n"), "stderr was:\n{}", output.stderr);
+}
+
+
+#[test]
+fn finally_runs_when_unwinding() {
+    assert_prints(
+        "(println (try 1 (finally (println 'cleanup))))
+         (println (try (try (error \"inner\") (finally (println 'inner-cleanup))) (catch e (error-message e))))
+         (println (try (try (error \"a\") (catch e (error \"b\")) (finally (println 'fin))) (catch e (error-message e))))",
+        "cleanup\n1\ninner-cleanup\n\"inner\"\nfin\n\"b\""
+    );
+}
+
+
+#[test]
+fn errors_unwind_deep_recursion() {
+    assert_prints(
+        "(defun (deep n) (if (== n 0) (error \"bottom\") (+ 1 (deep (- n 1)))))
+         (println (try (deep 10000) (catch e e)))
+         (defun (inf n) (+ 1 (inf n)))
+         (println (try (inf 1) (catch e e)))",
+        "<error: bottom>\n<error: maximum recursion depth of 1000000 exceeded>"
+    );
+}
+
+
+#[test]
+fn missing_files_are_errors_not_panics() {
+    let dir = common::scratch_dir(&[]);
+    let output = common::run_file(&dir.join("missing.lzp"), &[]);
+
+    assert!(output.stderr.contains("Liszp: cannot open file"), "stderr was:\n{}", output.stderr);
+    assert!(!output.stderr.contains("panicked"), "stderr was:\n{}", output.stderr);
+}
+
+
+#[test]
+fn uncaught_internal_errors_show_their_message() {
+    assert_fails("(defun (f x) (+ x \"s\")) (f 1)", "Liszp: '+' expression takes numeric arguments");
+}
@@ -0,0 +1,209 @@
+mod common;
+
+use common::{ assert_fails, assert_prints };
+
+
+/* Tail calls */
+
+
+#[test]
+fn tail_calls_run_in_constant_space() {
+    assert_prints(
+        "(defun (count-down n) (if (== n 0) 'done (count-down (- n 1))))
+         (println (count-down 200000))",
+        "done"
+    );
+}
+
+
+#[test]
+fn tail_calls_through_let() {
+    assert_prints(
+        "(defun (loop n) (let ([m (- n 1)]) (if (== m 0) 'done (loop m))))
+         (defun (build n acc) (if (== n 0) acc (build (- n 1) (cons n acc))))
+         (println (loop 100000))
+         (println (len (build 100000 '())))",
+        "done\n100000"
+    );
+}
+
+
+/* Evaluation stack */
+
+
+#[test]
+fn deep_recursion_does_not_overflow() {
+    assert_prints(
+        "(defun (build n acc) (if (== n 0) acc (build (- n 1) (cons n acc))))
+         (def big (build 20000 '()))
+         (println (len big))
+         (println (foldr (lambda (a b) (+ a b)) 0 big))
+         (println (equals? big (build 20000 '())))",
+        "20000\n200010000\ntrue"
+    );
+}
+
+
+#[test]
+fn unbounded_recursion_is_an_error() {
+    assert_fails("(defun (inf n) (+ 1 (inf n))) (inf 1)", "maximum recursion depth of 1000000 exceeded");
+}
+
+
+#[test]
+fn stack_trace_names_the_functions_called() {
+    assert_fails(
+        "(defun (bad x) (car x)) (defun (outer y) (+ 1 (bad y))) (outer 5)",
+        "stack trace:\n-> in function 'outer'\n-> in function 'bad'"
+    );
+}
+
+
+/* Parameters */
+
+
+#[test]
+fn rest_parameters_collect_remaining_arguments() {
+    assert_prints(
+        "(defun (my-list @ xs) xs)
+         (println (my-list 1 2 3))
+         (println (my-list))",
+        "(1 2 3)\nnil"
+    );
+}
+
+
+#[test]
+fn optional_parameters_have_defaults() {
+    assert_prints(
+        "(defun (greet name ? [greeting \"hello\"] punct) (list greeting name punct))
+         (println (greet \"bob\"))
+         (println (greet \"bob\" \"hi\" \"!\"))
+         (defun (f a ? [b (+ a 1)] @ rest) (list a b rest))
+         (println (f 1))
+         (println (f 1 5 6 7))",
+        "(\"hello\" \"bob\" nil)\n(\"hi\" \"bob\" \"!\")\n(1 2 nil)\n(1 5 (6 7))"
+    );
+}
+
+
+#[test]
+fn keyword_parameters_are_passed_by_name() {
+    assert_prints(
+        "(defun (join xs : [sep \", \"] [prefix \"\"]) (list prefix sep xs))
+         (println (join '(1 2)))
+         (println (join '(1 2) :sep \"-\"))
+         (println (join '(1 2) :prefix \">\" :sep \"-\"))
+         (println (try (join '(1 2) :bad 2) (catch e (error-message e))))",
+        "(\"\" \", \" (1 2))\n(\"\" \"-\" (1 2))\n(\">\" \"-\" (1 2))
+\"function has no keyword parameter ':bad' (parameters: (xs : [sep \", \"] [prefix \"\"]))\""
+    );
+}
+
+
+#[test]
+fn parameter_errors() {
+    assert_fails(
+        "(defun (greet name ? [greeting \"hello\"] punct) name) (greet)",
+        "function expected between 1 and 3 arguments but received 0 (parameters: (name ? [greeting \"hello\"] punct))"
+    );
+    assert_fails("(defun (g a ? b ? c) a)", "Liszp: unexpected '?' in function parameters");
+}
+
+
+/* Sequencing */
+
+
+#[test]
+fn do_and_begin_return_their_last_value() {
+    assert_prints("(println (do 1 2 3)) (println (begin))", "3\nnil");
+}
+
+
+#[test]
+fn bodies_are_evaluated_in_sequence() {
+    assert_prints(
+        "(defun (noisy x) (println x) (* x 2))
+         (println (noisy 4))
+         (println ((lambda (a) (println a) (+ a 1)) 1))
+         (println (let ([a 1] [b 2]) (println a) (+ a b)))",
+        "4\n8\n1\n2\n1\n3"
+    );
+}
+
+
+#[test]
+fn cond_branches_are_sequenced_with_do() {
+    assert_prints(
+        "(defun (sign n)
+             (cond
+                 (< n 0) (do (println n) 'negative)
+                 (== n 0) (begin (println n) 'zero)
+                 true 'positive))
+         (println (list (sign (- 0 5)) (sign 0) (sign 3)))",
+        "-5\n0\n(negative zero positive)"
+    );
+}
+
+
+#[test]
+fn cond_rejects_a_condition_without_a_branch() {
+    assert_fails("(cond (== 1 2) 1 true)", "improperly formatted cond expression");
+}
+
+
+#[test]
+fn cond_without_a_true_branch_is_an_error() {
+    assert_fails("(cond false 1)", "No branches of this cond expression were satisfied");
+}
+
+
+/* Mutation */
+
+
+#[test]
+fn set_assigns_to_the_binding_in_scope() {
+    assert_prints(
+        "(def counter 0)
+         (defun (incr!) (set! counter (+ counter 1)))
+         (incr!) (incr!)
+         (println counter)
+         (defun (make-counter) (let ([n 0]) (lambda () (set! n (+ n 1)) n)))
+         (def c (make-counter))
+         (def d (make-counter))
+         (c) (c)
+         (println (list (c) (d)))",
+        "2\n(3 1)"
+    );
+
+    assert_fails("(set! undefined-thing 1)", "cannot assign to unbound name 'undefined-thing'");
+}
+
+
+#[test]
+fn cons_cells_and_boxes_are_mutable() {
+    assert_prints(
+        "(def xs (list 1 2 3))
+         (set-car! xs 10)
+         (set-cdr! (cdr xs) '(30 40))
+         (println xs)
+         (def b (box 5))
+         (set-box! b (+ (unbox b) 1))
+         (println (list (unbox b) (box? b) (box? 6)))
+         (println b)",
+        "(10 2 30 40)\n(6 true false)\n<box 6>"
+    );
+}
+
+
+#[test]
+fn equality_compares_lists_by_structure_and_boxes_by_identity() {
+    assert_prints(
+        "(def b (box 1))
+         (println (list (equals? b b) (equals? (box 1) (box 1))))
+         (println (equals? '(1 2 (3)) '(1 2 (3))))
+         (println (list (equals? (cons 1 (cons 2 3)) (cons 1 (cons 2 3))) (equals? (cons 1 2) (cons 1 3))))
+         (println (cons 1 (cons 2 3)))",
+        "(true false)\ntrue\n(true false)\n(1 2 . 3)"
+    );
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use common::assert_prints;
+
+
+/* Dispatch */
+
+
+#[test]
+fn methods_are_chosen_by_argument_types() {
+    assert_prints(
+        "(defgeneric describe (x) 'something)
+         (defmethod describe ((x : int)) 'an-int)
+         (defmethod describe ((x : str)) 'a-string)
+         (defmethod describe ((x : cons)) (list 'a-list-of (describe (car x))))
+         (println (list (describe 1) (describe \"s\") (describe '(1 2)) (describe 'foo)))",
+        "(an-int a-string (a-list-of an-int) something)"
+    );
+}
+
+
+#[test]
+fn the_most_specific_method_wins() {
+    assert_prints(
+        "(defmethod collide ((a : int) b) 'int-any)
+         (defmethod collide (a (b : int)) 'any-int)
+         (defmethod collide ((a : int) (b : int)) 'int-int)
+         (deftype shape (circle r) (rect w h))
+         (defmethod area ((s : circle)) 'circle)
+         (defmethod area ((s : shape)) 'some-shape)
+         (println (list (collide 1 2) (collide 1 \"x\") (collide \"x\" 1)))
+         (println (list (area (circle 1)) (area (rect 1 2))))",
+        "(int-int int-any any-int)\n(circle some-shape)"
+    );
+}
+
+
+#[test]
+fn no_applicable_method_lists_the_candidates() {
+    assert_prints(
+        "(defmethod collide ((a : int) b) 'int-any)
+         (defmethod collide (a (b : int)) 'any-int)
+         (println (try (collide \"x\" \"y\") (catch e (error-message e))))",
+        "\"no method of 'collide' applies to arguments of types (str str), methods are: (collide (a : int) b), (collide a (b : int))\""
+    );
+}
+
+
+#[test]
+fn destructuring_parameters_are_not_specializers() {
+    assert_prints(
+        "(defmethod first-of ((a b)) a)
+         (defmethod first-of ((a : int)) a)
+         (println (list (first-of '(1 2)) (first-of 5)))",
+        "(1 5)"
+    );
+}
+
+
+/* Extending builtins */
+
+
+#[test]
+fn methods_extend_builtins() {
+    assert_prints(
+        "(defstruct vec x y)
+         (defmethod + ((a : vec) (b : vec)) (make-vec (+ (vec-x a) (vec-x b)) (+ (vec-y a) (vec-y b))))
+         (defmethod equals? ((a : vec) (b : vec)) (== (vec-x a) (vec-x b)))
+         (defmethod repr ((v : vec)) (list '< (vec-x v) (vec-y v) '>))
+         (println (+ (make-vec 1 2) (make-vec 10 20)))
+         (println (list (+ 1 2 3) (equals? (make-vec 1 2) (make-vec 1 5)) (equals? 1 2)))
+         (println (try (+ (make-vec 1 2) 3) (catch e (error-message e))))",
+        "(< 11 22 >)\n(6 true false)\n\"Liszp: '+' expression takes numeric arguments\""
+    );
+}
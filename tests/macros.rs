@@ -0,0 +1,237 @@
+mod common;
+
+use common::{ assert_fails, assert_prints };
+
+
+/* Quasiquote */
+
+
+#[test]
+fn quasiquote_unquotes_and_splices() {
+    assert_prints(
+        "(def xs (list 1 2 3))
+         (println `(a ,(car xs) ,@xs b))
+         (println `(0 ,@xs ,@xs))
+         (println `(,@xs))
+         (println `x)",
+        "(a 1 1 2 3 b)\n(0 1 2 3 1 2 3)\n(1 2 3)\nx"
+    );
+}
+
+
+#[test]
+fn quasiquote_dotted_unquote_tail() {
+    assert_prints(
+        "(def xs (list 1 2))
+         (println `(1 . ,xs))
+         (println (len `(1 . ,xs)))
+         (println (equals? `(1 . ,xs) '(1 1 2)))
+         (println `(0 ,@xs . ,xs))
+         (println `(x . ,(+ 1 2)))",
+        "(1 1 2)\n3\ntrue\n(0 1 2 1 2)\n(x . 3)"
+    );
+}
+
+
+#[test]
+fn nested_quasiquote_keeps_inner_unquotes() {
+    assert_prints(
+        "(def xs (list 1 2 3))
+         (println `(1 `(2 ,(3 ,(+ 1 3)))))
+         (println `(1 `(2 ,@(list ,@xs))))",
+        "(1 (quasiquote (2 (unquote (3 4)))))\n(1 (quasiquote (2 (unquote-splicing (list 1 2 3)))))"
+    );
+}
+
+
+#[test]
+fn quasiquote_uses_lexical_scope() {
+    assert_prints(
+        "(defun (f list) `(,list ,@list))
+         (defun (g y) (let ([z 10]) `(,y ,z)))
+         (println (f (list 9 8)))
+         (println (g 5))",
+        "((9 8) 9 8)\n(5 10)"
+    );
+}
+
+
+#[test]
+fn cond_expands_without_evaluating() {
+    assert_prints(
+        "(println (macroexpand-1 '(cond a 1 b 2)))
+         (def a false)
+         (def b true)
+         (println (cond a 1 b 2))",
+        "(if a 1 (cond b 2))\n2"
+    );
+}
+
+
+/* syntax-rules */
+
+
+#[test]
+fn syntax_rules_binders_are_renamed() {
+    assert_prints(
+        "(define-syntax swap!
+           (syntax-rules ()
+             ((_ a b) (let ([tmp a]) (set! a b) (set! b tmp)))))
+         (def tmp 1)
+         (def other 2)
+         (swap! tmp other)
+         (println (list tmp other))",
+        "(2 1)"
+    );
+}
+
+
+#[test]
+fn syntax_rules_ellipses_and_literals() {
+    assert_prints(
+        "(define-syntax my-or
+           (syntax-rules ()
+             ((_) false)
+             ((_ e) e)
+             ((_ e r ...) (let ([t e]) (if t t (my-or r ...))))))
+         (def t 5)
+         (println (list (my-or) (my-or false) (my-or false t)))
+         (define-syntax for
+           (syntax-rules (in)
+             ((_ x in xs body ...) (map xs (lambda (x) body ...)))))
+         (println (for y in (list 1 2 3) (* y y)))",
+        "(false false 5)\n(1 4 9)"
+    );
+}
+
+
+#[test]
+fn syntax_rules_free_names_refer_to_the_definition() {
+    assert_prints(
+        "(define-syntax twice (syntax-rules () ((_ e) (double (double e)))))
+         (defun (double x) (* 2 x))
+         (defun (f double) (twice double))
+         (println (f 3))
+         (def total 0)
+         (define-syntax add! (syntax-rules () ((_ n) (set! total (+ total n)))))
+         (defun (h total) (add! total) total)
+         (println (list (h 5) total))",
+        "12\n(5 5)"
+    );
+}
+
+
+#[test]
+fn syntax_rules_binders_of_every_binding_form_are_renamed() {
+    assert_prints(
+        "(define-syntax first-or (syntax-rules () ((_ xs d) (match xs ((list head . _) (list head d)) (_ d)))))
+         (def head 'default)
+         (println (list (first-or '(1 2) head) (first-or nil head)))
+         (define-syntax plus-one (syntax-rules () ((_ e) (reset (+ 1 (shift k (k e)))))))
+         (def k 10)
+         (println (plus-one k))
+         (define-syntax or-use (syntax-rules () ((_ e d) (restart-case e (use (v) (list v d))))))
+         (def v 'outer)
+         (println (or-use (invoke-restart 'use 1) v))
+         (define-syntax counting (syntax-rules () ((_ start) (generator (def n start) (yield n) (yield (+ n 1))))))
+         (def n 100)
+         (def gen (counting 1))
+         (println (list (gen) (gen) n))",
+        "((1 default) default)\n11\n(1 outer)\n(1 2 100)"
+    );
+}
+
+
+/* Inspecting expansions */
+
+
+#[test]
+fn macroexpand_expands_the_head_of_an_expression() {
+    assert_prints(
+        "(println (macroexpand-1 '(let ([a 1] [b 2]) (+ a b))))
+         (println (macroexpand '(+ 1 2)))
+         (println (macroexpand 5))
+         (define-syntax my-if (syntax-rules () ((_ c a b) (cond c a true b))))
+         (println (macroexpand-1 '(my-if x 1 2)))
+         (println (macroexpand '(my-if x 1 2)))",
+        "((lambda (a) ((lambda (b) (do (+ a b))) 2)) 1)\n(+ 1 2)\n5\n(cond x 1 true 2)\n(if x 1 (cond true 2))"
+    );
+}
+
+
+#[test]
+fn macros_are_expanded_once_before_evaluation() {
+    assert_prints(
+        "(defmacro (twice x) `(do ,x ,x))
+         (defun (f) (twice (println 'hi)))
+         (f)
+         (println (eval (list 'twice 5)))
+         (println (eval '(let ([a 2]) (* a 3))))",
+        "hi\nhi\n5\n6"
+    );
+}
+
+
+#[test]
+fn expansions_can_be_traced() {
+    let dir = common::scratch_dir(&[ ("main.lzp", "(defmacro (twice x) `(do ,x ,x)) (println (twice 1))") ]);
+    let executable = std::path::Path::new(env!("CARGO_BIN_EXE_liszp"));
+
+    let output = common::run_liszp(executable, &[ "main.lzp", "--trace-expansions" ], &dir, &[]);
+
+    assert_eq!(output.stdout.trim_end(), "1");
+    assert_eq!(output.stderr.trim_end(), "expanding macro 'twice': (twice 1) => (do 1 1)");
+
+    let output = common::run_liszp(executable, &[ "main.lzp" ], &dir, &[]);
+
+    assert_eq!(output.stderr, "");
+}
+
+
+/* Redefinition and local macros */
+
+
+#[test]
+fn macros_can_be_redefined_and_removed() {
+    assert_prints(
+        "(defmacro (twice x) `(do ,x ,x))
+         (twice (println 1))
+         (defmacro (twice x) `(do ,x ,x ,x))
+         (twice (println 2))
+         (undefmacro twice)
+         (def twice (lambda (x) (* 2 x)))
+         (println (twice 4))",
+        "1\n1\n2\n2\n2\n8"
+    );
+}
+
+
+#[test]
+fn redefining_a_macro_can_warn() {
+    let dir = common::scratch_dir(&[ ("main.lzp", "(defmacro (m x) x) (defmacro (m x) x)") ]);
+    let executable = std::path::Path::new(env!("CARGO_BIN_EXE_liszp"));
+
+    let output = common::run_liszp(executable, &[ "main.lzp", "--warn-macro-redefinition" ], &dir, &[]);
+
+    assert_eq!(output.stderr.trim_end(), "Liszp: warning: redefining macro 'm'");
+
+    let output = common::run_liszp(executable, &[ "main.lzp" ], &dir, &[]);
+
+    assert_eq!(output.stderr, "");
+}
+
+
+#[test]
+fn macrolet_defines_macros_within_its_body() {
+    assert_prints(
+        "(defmacro (m x) `(list 'global ,x))
+         (println (macrolet (((m x) `(list 'local ,x))
+                             (sq (syntax-rules () ((_ x) (* x x)))))
+                    (println (m 1))
+                    (sq 3)))
+         (println (m 2))",
+        "(local 1)\n9\n(global 2)"
+    );
+
+    assert_fails("(macrolet ((sq (syntax-rules () ((_ x) (* x x))))) 1) (sq 3)", "value 'sq' is undefined");
+}
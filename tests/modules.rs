@@ -0,0 +1,258 @@
+mod common;
+
+use common::{ run_file, scratch_dir, Output };
+use std::path::Path;
+
+
+fn run_project(files: &[(&str, &str)]) -> Output {
+    /* Runs main.lzp from a directory holding some files */
+
+    let dir = scratch_dir(files);
+
+    run_file(&dir.join("main.lzp"), &[])
+}
+
+
+fn assert_project_prints(files: &[(&str, &str)], expected: &str) {
+    /* Asserts that main.lzp runs without error and prints some lines */
+
+    let output = run_project(files);
+
+    assert_eq!(output.stderr, "", "unexpected error");
+    assert_eq!(output.stdout.trim_end(), expected.trim_end());
+}
+
+
+/* Imports and exports */
+
+
+#[test]
+fn imports_bind_qualified_and_selected_names() {
+    assert_project_prints(
+        &[
+            ("geometry.lzp", "(module geometry (export area perimeter))
+                              (def pi 3)
+                              (defun (area r) (* pi (* r r)))
+                              (defun (perimeter r) (* 2 (* pi r)))"),
+            ("main.lzp", "(import \"geometry.lzp\" area)
+                          (println (geometry/area 2))
+                          (println (area 1))
+                          (println (geometry/perimeter 1))
+                          (import \"geometry.lzp\" :as g (perimeter :as circumference))
+                          (println (list (g/area 1) (circumference 2)))")
+        ],
+        "12\n3\n6\n(3 12)"
+    );
+}
+
+
+#[test]
+fn modules_only_export_what_they_declare() {
+    let output = run_project(&[
+        ("secret.lzp", "(module secret (export shown)) (def shown 1) (def hidden 2)"),
+        ("main.lzp", "(import \"secret.lzp\") (println secret/shown) (println secret/hidden)")
+    ]);
+
+    assert_eq!(output.stdout.trim_end(), "1");
+    assert!(output.stderr.contains("value 'secret/hidden' is undefined"), "stderr was:\n{}", output.stderr);
+
+    let output = run_project(&[
+        ("secret.lzp", "(module secret (export shown)) (def shown 1)"),
+        ("main.lzp", "(import \"secret.lzp\" hidden)")
+    ]);
+
+    assert!(output.stderr.contains("module 'secret' does not export 'hidden'"), "stderr was:\n{}", output.stderr);
+}
+
+
+#[test]
+fn modules_have_their_own_namespaces() {
+    assert_project_prints(
+        &[
+            ("counter.lzp", "(def count 10) (defun (get-count) count)"),
+            ("main.lzp", "(def count 1)
+                          (import \"counter.lzp\")
+                          (println (list count (counter/get-count)))")
+        ],
+        "(1 10)"
+    );
+}
+
+
+#[test]
+fn imports_see_assignments_to_exported_variables() {
+    assert_project_prints(
+        &[
+            ("counter.lzp", "(module counter (export count increment!))
+                             (def count 0)
+                             (defun (increment!) (set! count (+ count 1)))"),
+            ("main.lzp", "(import \"counter.lzp\" count)
+                          (println (list count counter/count))
+                          (counter/increment!)
+                          (counter/increment!)
+                          (println (list count counter/count))
+                          (set! count 10)
+                          (println (list count (counter/increment!) counter/count))")
+        ],
+        "(0 0)\n(2 2)\n(10 nil 11)"
+    );
+}
+
+
+#[test]
+fn modules_are_loaded_once() {
+    assert_project_prints(
+        &[
+            ("noisy.lzp", "(println 'loading) (def value 5)"),
+            ("other.lzp", "(import \"noisy.lzp\") (def value noisy/value)"),
+            ("main.lzp", "(import \"noisy.lzp\")
+                          (import \"other.lzp\")
+                          (import \"noisy.lzp\")
+                          (println (list noisy/value other/value))")
+        ],
+        "loading\n(5 5)"
+    );
+}
+
+
+#[test]
+fn cyclic_imports_are_errors() {
+    let output = run_project(&[
+        ("a.lzp", "(import \"b.lzp\")"),
+        ("b.lzp", "(import \"a.lzp\")"),
+        ("main.lzp", "(import \"a.lzp\")")
+    ]);
+
+    assert!(output.stderr.contains("cyclic import:"), "stderr was:\n{}", output.stderr);
+    assert!(output.stderr.contains("a.lzp -> ") && output.stderr.contains("b.lzp -> "), "stderr was:\n{}", output.stderr);
+}
+
+
+/* Namespaced extensions */
+
+
+#[test]
+fn extending_a_builtin_leaves_other_namespaces_alone() {
+    assert_project_prints(
+        &[
+            ("adder.lzp", "(defun (add a b) (+ a b))"),
+            ("main.lzp", "(defstruct vec2 x y)
+                          (defmethod + ((a : vec2) (b : vec2)) (make-vec2 (+ (vec2-x a) (vec2-x b)) (+ (vec2-y a) (vec2-y b))))
+                          (import \"adder.lzp\")
+                          (println (vec2-y (+ (make-vec2 1 2) (make-vec2 3 4))))
+                          (println (len (list 1 2 3)))
+                          (println (stream-take 3 (stream-from 1 2)))
+                          (println (adder/add 1 2))")
+        ],
+        "6\n3\n(1 3 5)\n3"
+    );
+}
+
+
+#[test]
+fn macros_are_scoped_to_the_file_defining_them() {
+    assert_project_prints(
+        &[
+            ("lib.lzp", "(defmacro (tag x) `(list 'lib ,x))
+                         (defun (tagged) (tag 1))"),
+            ("main.lzp", "(defmacro (tag x) `(list 'main ,x))
+                          (import \"lib.lzp\")
+                          (println (lib/tagged))
+                          (println (tag 2))")
+        ],
+        "(lib 1)\n(main 2)"
+    );
+}
+
+
+/* Search path and packages */
+
+
+#[test]
+fn modules_are_found_in_lib_and_liszp_path() {
+    let shared = scratch_dir(&[ ("shared.lzp", "(def origin 'liszp-path)"), ("local.lzp", "(def origin 'shadowed)") ]);
+    let project = scratch_dir(&[
+        ("lib/local.lzp", "(def origin 'lib)"),
+        ("main.lzp", "(import \"local\") (import \"shared\") (println (list local/origin shared/origin))")
+    ]);
+
+    let output = run_file(&project.join("main.lzp"), &[ ("LISZP_PATH", shared.to_str().unwrap()) ]);
+
+    assert_eq!(output.stderr, "");
+    assert_eq!(output.stdout.trim_end(), "(lib liszp-path)");
+
+    let output = run_file(&project.join("main.lzp"), &[]);
+
+    assert!(output.stderr.contains("cannot find module 'shared'"), "stderr was:\n{}", output.stderr);
+}
+
+
+#[test]
+fn packages_check_their_modules_and_dependencies() {
+    let files = [
+        ("lib/shapes/package.lzp", "(package shapes (version \"1.0\") (modules circle) (dependencies maths))"),
+        ("lib/shapes/circle.lzp", "(import \"maths/consts\") (defun (area r) (* consts/pi (* r r)))"),
+        ("lib/shapes/internal.lzp", "(def x 1)"),
+        ("lib/maths/package.lzp", "(package maths (modules consts))"),
+        ("lib/maths/consts.lzp", "(def pi 3)")
+    ];
+
+    let mut with_main = files.to_vec();
+    with_main.push(("main.lzp", "(import \"shapes/circle\") (println (circle/area 2))"));
+
+    assert_project_prints(&with_main, "12");
+
+    let mut unlisted = files.to_vec();
+    unlisted.push(("main.lzp", "(import \"shapes/internal\")"));
+
+    let output = run_project(&unlisted);
+
+    assert!(output.stderr.contains("package 'shapes' has no module 'internal', its modules are: circle"), "stderr was:\n{}", output.stderr);
+
+    let output = run_project(&[ files[0], files[1], ("main.lzp", "(import \"shapes/circle\")") ]);
+
+    assert!(output.stderr.contains("package 'shapes' depends on 'maths', which is not in"), "stderr was:\n{}", output.stderr);
+}
+
+
+#[test]
+fn modules_subcommand_lists_importable_modules() {
+    let project = scratch_dir(&[
+        ("lib/util.lzp", "(def x 1)"),
+        ("lib/shapes/package.lzp", "(package shapes (version \"1.0\") (modules circle) (dependencies maths))"),
+        ("lib/shapes/circle.lzp", "(def r 1)"),
+        ("main.lzp", "(println 1)")
+    ]);
+
+    let output = common::run_liszp(Path::new(env!("CARGO_BIN_EXE_liszp")), &[ "modules", "main.lzp" ], &project, &[]);
+
+    assert_eq!(output.stderr, "");
+    assert!(output.stdout.starts_with("search path:\n"), "stdout was:\n{}", output.stdout);
+    assert!(output.stdout.contains("util "), "stdout was:\n{}", output.stdout);
+    assert!(output.stdout.contains("shapes/circle "), "stdout was:\n{}", output.stdout);
+    assert!(output.stdout.contains("(package shapes 1.0, missing dependency 'maths')"), "stdout was:\n{}", output.stdout);
+    assert!(!output.stdout.contains("std-functions") && !output.stdout.contains("std-macros"), "stdout was:\n{}", output.stdout);
+}
+
+
+#[test]
+fn stdlib_is_found_next_to_an_installed_executable() {
+    let stdlib = concat!(env!("CARGO_MANIFEST_DIR"), "/liszp-stdlib");
+    let macros = std::fs::read_to_string(format!("{}/std-macros.lzp", stdlib)).unwrap();
+    let functions = std::fs::read_to_string(format!("{}/std-functions.lzp", stdlib)).unwrap();
+    let functions = format!("{}\n(def stdlib-location 'installed)\n", functions);
+
+    let prefix = scratch_dir(&[
+        ("share/liszp/liszp-stdlib/std-macros.lzp", &macros),
+        ("share/liszp/liszp-stdlib/std-functions.lzp", &functions),
+        ("main.lzp", "(println stdlib-location)")
+    ]);
+
+    std::fs::create_dir_all(prefix.join("bin")).unwrap();
+    std::fs::copy(env!("CARGO_BIN_EXE_liszp"), prefix.join("bin/liszp")).unwrap();
+
+    let output = common::run_liszp(&prefix.join("bin/liszp"), &[ "main.lzp" ], &prefix, &[]);
+
+    assert_eq!(output.stderr, "");
+    assert_eq!(output.stdout.trim_end(), "installed");
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use common::assert_prints;
+
+
+/* match */
+
+
+#[test]
+fn match_literals_lists_and_guards() {
+    assert_prints(
+        "(defun (describe x)
+           (match x
+             (0 \"zero\")
+             (\"hi\" \"a greeting\")
+             (:key 'keyword)
+             ('foo 'quoted-foo)
+             (nil 'empty)
+             ((list a) (list 'one a))
+             ((list a b . rest) :when (> a b) (list 'descending a b rest))
+             ((cons h t) (list 'cons h t))
+             (n :when (int? n) (list 'int n))
+             (_ 'other)))
+         (println (map (list 0 \"hi\" :key 'foo nil '(1) '(3 2 1) '(1 2 3) 7 true) describe))
+         (println (match '(1 (2 3)) ((list x (list y z)) (+ x (+ y z)))))
+         (println (match 3 (x :when (> x 5) 'big) (x :when (> x 1) 'medium) (_ 'small)))",
+        "(\"zero\" \"a greeting\" keyword quoted-foo empty (one 1) (descending 3 2 (1)) (cons 1 (2 3)) (int 7) other)\n6\nmedium"
+    );
+}
+
+
+#[test]
+fn match_errors() {
+    assert_prints(
+        "(println (try (match 5 (4 'four)) (catch e (error-message e))))
+         (println (try (match 5 (x 1) (4 2)) (catch e (error-message e))))
+         (println (try (match 5 ((list a _) 1) ((list 1 2) 2)) (catch e (error-message e))))
+         (println (try (match 5 ((list a a) 1)) (catch e (error-message e))))
+         (println (try (match 3 (x :when 1 'big)) (catch e (error-message e))))",
+        "\"no clause of match expression matched '5'\"
+\"Liszp: clause 2 of match expression is unreachable: '(4 2)'\"
+\"Liszp: clause 2 of match expression is unreachable: '((list 1 2) 2)'\"
+\"Liszp: name 'a' is bound more than once in pattern\"
+\"guard of match clause expected a boolean, received '1'\""
+    );
+}
+
+
+/* Destructuring */
+
+
+#[test]
+fn parameters_and_let_destructure_lists() {
+    assert_prints(
+        "(def f (lambda ((a b) c) (list a b c)))
+         (println (f '(1 2) 3))
+         (println (let ([(x . rest) '(1 2 3)] [y 4]) (list x rest y)))
+         (println (let ([((a _) . (b)) '((1 2) 3)]) (+ a b)))
+         (defun (swap (a . b)) (cons b a))
+         (println (swap '(1 2)))",
+        "(1 2 3)\n(1 (2 3) 4)\n4\n((2) . 1)"
+    );
+}
+
+
+#[test]
+fn destructuring_errors() {
+    assert_prints(
+        "(def f (lambda ((a b) c) (list a b c)))
+         (println (try (f '(1) 3) (catch e (error-message e))))
+         (println (try (f '(1 2 3) 3) (catch e (error-message e))))
+         (println (try (f 5 3) (catch e (error-message e))))
+         (println (try (lambda ((a a)) 1) (catch e (error-message e))))
+         (println (try (lambda ((a 1)) 1) (catch e (error-message e))))
+         (println (try (f 1) (catch e (error-message e))))",
+        "\"cannot destructure '(1)' with parameter '(a b)': list has too few elements\"
+\"cannot destructure '(1 2 3)' with parameter '(a b)': list has too many elements\"
+\"cannot destructure '5' with parameter '(a b)': expected a list but received '5'\"
+\"Liszp: name 'a' is bound more than once in pattern\"
+\"Liszp: expected a name or a list of names in destructuring parameter, received '1'\"
+\"function expected 2 arguments but received 1 (parameters: ((a b) c))\""
+    );
+}
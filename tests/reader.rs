@@ -0,0 +1,49 @@
+mod common;
+
+use common::{ assert_fails, assert_prints };
+
+
+/* Comments */
+
+
+#[test]
+fn block_comments_span_lines() {
+    assert_prints(
+        "#| a block comment
+            spanning lines (with brackets |#
+         (println (list 1 #| inline |# 2))
+         # ordinary comment
+         (println (list #| c |#))",
+        "(1 2)\nnil"
+    );
+}
+
+
+/* Reader macros */
+
+
+#[test]
+fn dispatch_macros_transform_what_follows_them() {
+    assert_prints(
+        "(set-dispatch-macro! \"{\" (lambda (xs) (cons (quote list) xs)))
+         (println #{1 2 (+ 1 2)})
+         (println '#{1 2})
+         (set-dispatch-macro! 'r (lambda (s) (list 'quote (list 'regex s))))
+         (println #r\"a+b\")",
+        "(1 2 3)\n(list 1 2)\n(regex \"a+b\")"
+    );
+}
+
+
+#[test]
+fn dispatch_macros_do_not_change_comments() {
+    assert_prints(
+        "(set-dispatch-macro! 'r (lambda (s) (list 'quote s)))
+         # risky comment
+         ## r
+         (println #r 1)
+         (println (try (set-dispatch-macro! \"#\" (lambda (x) x)) (catch e (error-message e))))",
+        "1\n\"Liszp: set-dispatch-macro! expected a single character which doesn't start a comment, received '\"#\"'\""
+    );
+    assert_fails("#risky comment", "no reader macro is defined for '#r' (comments start with '# ')");
+}
@@ -0,0 +1,130 @@
+mod common;
+
+use common::assert_prints;
+
+
+/* Structs */
+
+
+#[test]
+fn structs_have_constructors_predicates_and_accessors() {
+    assert_prints(
+        "(defstruct point x y)
+         (def p (make-point 1 2))
+         (println p)
+         (println (list (point? p) (point? 5) (point-x p) (point-y p)))
+         (println (make-point :y 5))
+         (println (list (equals? (make-point 1 2) p) (equals? (make-point 1 3) p)))
+         (defstruct other x y)
+         (println (equals? (make-other 1 2) p))
+         (println (map (list (make-point 1 2) (make-point 3 4)) point-x))
+         (println (match p ((cons a b) 1) (x :when (point? x) (point-y x))))",
+        "(make-point :x 1 :y 2)\n(true false 1 2)\n(make-point :x nil :y 5)\n(true false)\nfalse\n(1 3)\n2"
+    );
+}
+
+
+#[test]
+fn mutable_structs_have_setters() {
+    assert_prints(
+        "(defstruct (counter :mutable) n)
+         (def c (make-counter 0))
+         (set-counter-n! c (+ 1 (counter-n c)))
+         (println c)
+         (defstruct point x y)
+         (println (try (set-point-x! (make-point 1 2) 1) (catch e (error-message e))))",
+        "(make-counter :n 1)\n\"value 'set-point-x!' is undefined\""
+    );
+}
+
+
+#[test]
+fn struct_errors() {
+    assert_prints(
+        "(defstruct point x y)
+         (defstruct other x y)
+         (println (try (other-x (make-point 1 2)) (catch e (error-message e))))
+         (println (try (make-point 1) (catch e (error-message e))))
+         (println (try (make-point :z 1) (catch e (error-message e))))
+         (println (try (defstruct p x x) (catch e (error-message e))))",
+        "\"function 'other-x' expected a struct of type 'other', received '(make-point :x 1 :y 2)'\"
+\"function 'make-point' expected 2 arguments but received 1\"
+\"struct 'point' has no field 'z'\"
+\"Liszp: field 'x' appears more than once in struct 'p'\""
+    );
+}
+
+
+/* Sum types */
+
+
+#[test]
+fn variants_have_constructors_predicates_and_accessors() {
+    assert_prints(
+        "(println (list none (some 5) (option? none) (option? (some 1)) (option? 3)))
+         (println (list (some? none) (none? none) (some-value (some 7))))
+         (println (equals? (some '(1 2)) (some '(1 2))))",
+        "(none (some 5) true true false)\n(false true 7)\ntrue"
+    );
+}
+
+
+#[test]
+fn match_on_variants() {
+    assert_prints(
+        "(deftype shape (circle r) (rect w h))
+         (defun (area s) (match s ((circle r) (* 3 (* r r))) ((rect w h) (* w h))))
+         (println (map (list (circle 2) (rect 3 4)) area))",
+        "(12 12)"
+    );
+}
+
+
+#[test]
+fn match_clauses_are_parsed_once_and_reparsed_for_new_types() {
+    assert_prints(
+        "(defun (describe c) (match c (red 'stop) (green 'go)))
+         (deftype light red green)
+         (println (map (list red green) describe))
+         (deftype light red green amber)
+         (println (try (describe amber) (catch e (error-message e))))
+         (defun (sum-areas n acc) (if (== n 0) acc (sum-areas (- n 1) (+ acc (match (some 2) ((some x) (* x x)) (none 0))))))
+         (println (sum-areas 1000 0))",
+        "(stop go)\n\"Liszp: match expression on type 'light' is not exhaustive: missing 'amber'\"\n4000"
+    );
+}
+
+
+#[test]
+fn non_exhaustive_matches_are_errors() {
+    assert_prints(
+        "(println (try (match (some 1) ((some v) v)) (catch e (error-message e))))
+         (println (try (match (some 1) ((some 1) 'one) (none 'nothing)) (catch e (error-message e))))
+         (println (try (match none (none 1) (none 2)) (catch e (error-message e))))",
+        "\"Liszp: match expression on type 'option' is not exhaustive: missing 'none'\"
+\"Liszp: match expression on type 'option' is not exhaustive: missing 'some'\"
+\"Liszp: clause 2 of match expression is unreachable: '(none 2)'\""
+    );
+}
+
+
+#[test]
+fn type_of_names_builtin_and_user_types() {
+    assert_prints(
+        "(deftype shape (circle r))
+         (println (map (list 1 \"s\" 'a nil '(1) true (some 1) (circle 1) (box 1)) (lambda (x) (type-of x))))",
+        "(int str name nil cons bool option shape box)"
+    );
+}
+
+
+#[test]
+fn partition_returns_a_partitioned_value() {
+    assert_prints(
+        "(def p (partition (lambda (x) (> x 2)) (list 1 5 2 7 3)))
+         (println p)
+         (println (list (partition? p) (partitioned-matching p) (partitioned-others p)))
+         (println (match p ((partitioned big small) (list 'big big 'small small))))",
+        "(partitioned (5 7 3) (1 2))\n(true (5 7 3) (1 2))\n(big (5 7 3) small (1 2))"
+    );
+}